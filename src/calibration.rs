@@ -0,0 +1,131 @@
+use serde::Deserialize;
+
+use crate::{data::InputFile, math};
+
+/// How many millimeters are in an inch, for converting from DPI.
+const MM_PER_INCH: f32 = 25.4;
+
+/// Whether calibrated areas are shown along with pixel areas,
+/// or in place of them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CalibratedReport {
+	#[default]
+	Alongside,
+	Instead,
+}//end enum CalibratedReport
+
+/// Settings for converting areas from pixels to mm².
+/// At most one of dpi, mm_per_pixel or reference should be given.
+/// If none are, areas are left in pixels.
+#[derive(Clone, PartialEq, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct CalibrationSettings {
+	/// The resolution of the scanner, in dots per inch.
+	pub dpi: Option<f32>,
+	/// The width of a single pixel, in millimeters.
+	pub mm_per_pixel: Option<f32>,
+	/// Part of the file id of a reference object scanned along
+	/// with the samples, like "ref". It's matched against whole
+	/// components of file ids, separated by dashes and dots, ignoring
+	/// case, so "ref" matches ns-ref-1.tif but not pref-ag05.tif.
+	/// The average Area1 of its lines
+	/// is taken to be reference_area, and it's left out of the output.
+	pub reference: Option<String>,
+	/// The known area of the reference object, in mm².
+	pub reference_area: Option<f32>,
+	/// Whether mm² values are shown along with pixel values, or instead of them.
+	pub report: CalibratedReport,
+}//end struct CalibrationSettings
+
+/// Whether the components of reference, separated by dashes and
+/// dots, appear one after another among the components of file_id,
+/// ignoring case.
+fn is_reference_id(file_id: &str, reference: &str) -> bool {
+	let file_components: Vec<&str> = file_id.split(&['-','.']).collect();
+	let ref_components: Vec<&str> = reference.split(&['-','.']).collect();
+	file_components.windows(ref_components.len())
+		.any(|window| window.iter().zip(ref_components.iter()).all(|(a, b)| a.eq_ignore_ascii_case(b)))
+}//end is_reference_id()
+
+/// The conversion from pixels to mm² for the files of a csv.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Calibration {
+	/// The area of a single pixel, in mm².
+	pub mm2_per_px: f32,
+	/// Whether mm² values are shown along with pixel values, or instead of them.
+	pub report: CalibratedReport,
+	/// Where mm2_per_px came from, to record in the output.
+	pub source: String,
+}//end struct Calibration
+
+impl CalibrationSettings {
+	/// Checks that at most one way of calibrating is given, that
+	/// it's complete, and that its values are positive.
+	pub fn check(&self) -> Result<(), String> {
+		let methods = [self.dpi.is_some(), self.mm_per_pixel.is_some(), self.reference.is_some()];
+		if methods.iter().filter(|given| **given).count() > 1 {
+			return Err("Only one of dpi, mm_per_pixel or reference can be used for calibration".to_string());
+		}//end if more than one method was given
+		if self.reference.is_some() != self.reference_area.is_some() {
+			return Err("Calibration by reference needs both reference and reference_area".to_string());
+		}//end if the reference method is incomplete
+		for (name, value) in [("dpi", self.dpi), ("mm_per_pixel", self.mm_per_pixel), ("reference_area", self.reference_area)] {
+			if value.is_some_and(|value| !(value > 0.)) {return Err(format!("Calibration {} must be more than 0", name));}
+		}//end checking each value is positive
+		return Ok(());
+	}//end check()
+
+	/// Figures out the conversion from pixels to mm² for data, or
+	/// None if these settings don't ask for calibration.
+	/// When calibrating by reference, lines of files whose id has
+	/// the reference as a component are used, then removed from data.
+	/// Fails if there's no usable reference in data.
+	///
+	/// # Examples
+	/// ```
+	/// use milo_excel_helper::{calibration::CalibrationSettings, data::{InputFile, InputLine, OrderingDetection}};
+	/// let settings = CalibrationSettings {dpi: Some(254.), ..Default::default()};
+	/// let calibration = settings.calibrate(&mut Vec::new()).unwrap().unwrap();
+	/// assert!((calibration.mm2_per_px - 0.01).abs() < 1e-6);
+	/// 
+	/// let file = |id: &str, area1| InputFile::new(id, vec![InputLine {grid_idx: 1, area1: Some(area1), ..Default::default()}], OrderingDetection::default());
+	/// let mut data = vec![file("ns-REF-1.tif", 100), file("pref-ag05-ab15.tif", 1000)];
+	/// let settings = CalibrationSettings {reference: Some("ref".to_string()), reference_area: Some(1.), ..Default::default()};
+	/// let calibration = settings.calibrate(&mut data).unwrap().unwrap();
+	/// assert!((calibration.mm2_per_px - 0.01).abs() < 1e-6);
+	/// assert_eq!(data.len(), 1);
+	/// ```
+	pub fn calibrate(&self, data: &mut Vec<InputFile>) -> Result<Option<Calibration>, String> {
+		let (mm2_per_px, source) = match (self.dpi, self.mm_per_pixel, &self.reference, self.reference_area) {
+			(Some(dpi), _, _, _) => ((MM_PER_INCH / dpi).powi(2), format!("{} DPI", dpi)),
+			(_, Some(mm_per_pixel), _, _) => (mm_per_pixel.powi(2), format!("{} mm per pixel", mm_per_pixel)),
+			(_, _, Some(reference), Some(reference_area)) => {
+				let is_reference = |file: &InputFile| is_reference_id(&file.file_id, reference);
+				let areas: Vec<Option<f32>> = data.iter()
+					.filter(|file| is_reference(file))
+					.flat_map(|file| file.input_lines.iter().map(|line| line.area1.map(|area| area as f32)))
+					.collect();
+				if math::count(&areas) == 0 {
+					return Err(format!("No reference object with {} as part of its file id was found, so areas are left in pixels", reference));
+				}//end if we don't have a reference to go by
+				let ref_ids: Vec<String> = data.iter().filter(|file| is_reference(file)).map(|file| file.file_id.clone()).collect();
+				data.retain(|file| !is_reference(file));
+				(reference_area / math::avg(&areas), format!("{} at {} mm²", ref_ids.join(", "), reference_area))
+			},
+			_ => return Ok(None),
+		};//end matching which way we're calibrating
+		if !mm2_per_px.is_finite() || mm2_per_px <= 0. {
+			return Err(format!("Calibrating from {} doesn't give a usable scale, so areas are left in pixels", source));
+		}//end if the scale doesn't make sense
+		return Ok(Some(Calibration {mm2_per_px, report: self.report, source}));
+	}//end calibrate()
+}//end impl for CalibrationSettings
+
+impl Calibration {
+	/// Describes this calibration for the output, like
+	/// 0.000448 mm² per pixel (from 1200 DPI).
+	pub fn describe(&self) -> String {
+		format!("{:.6} mm² per pixel (from {})", self.mm2_per_px, self.source)
+	}//end describe()
+}//end impl for Calibration
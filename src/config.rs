@@ -0,0 +1,140 @@
+use std::{env, error::Error, fmt, fs, io, path::{Path, PathBuf}};
+
+use serde::Deserialize;
+
+use crate::{calibration::CalibrationSettings, data::{ColumnAliases, Orderings, SampleOrder}, excel::OutputSettings, exclusion::ExclusionRules, infer::InferenceSettings};
+
+/// The name of the file we look for settings in.
+pub const CONFIG_FILE_NAME: &str = "milo-config.toml";
+
+/// Settings which can be changed by the user in a toml file.  
+/// Anything left out of the file keeps its default value.
+/// 
+/// # Examples
+/// ```
+/// use milo_excel_helper::config::Config;
+/// let config = Config::from_toml(r#"
+/// [columns]
+/// area1 = ["Kernel Px"]
+/// 
+/// [[orderings]]
+/// name = "AB13"
+/// indicators = ["ab13"]
+/// labels = ["1a","1b","1c","2a","2b","2c","3a","3b","3c"]
+/// 
+/// [output]
+/// alignment = "grid_idx"
+/// metrics = ["%Area2", "Floury"]
+/// 
+/// [[output.derived]]
+/// name = "Floury"
+/// expr = "area1 - area2"
+/// decimals = 0
+/// 
+/// [[output.thresholds]]
+/// column = "CV"
+/// above = 0.1
+/// 
+/// [calibration]
+/// dpi = 600
+/// 
+/// [exclusion]
+/// min_area1 = 500 # in pixels, even with calibration on
+/// "#).unwrap();
+/// assert_eq!(config.columns.area1, vec!["Kernel Px".to_string()]);
+/// assert_eq!(config.output.alignment, milo_excel_helper::data::Alignment::GridIdx);
+/// assert_eq!(config.output.metrics[1].name(), "Floury");
+/// assert_eq!(config.output.thresholds[0].above, Some(0.1));
+/// assert_eq!(config.exclusion.min_area1, Some(500));
+/// let orderings = config.get_orderings();
+/// assert_eq!(orderings.from_file_id("ns-ag05-ab13.tif").labels.len(), 9);
+/// assert_eq!(orderings.from_file_id("ns-ag05-ab15.tif").name, "AB15");
+/// ```
+#[derive(Clone, PartialEq, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	/// Names accepted for each column of the csv header row.
+	pub columns: ColumnAliases,
+	/// Custom sample orderings, checked before the built-in ones.
+	/// An ordering with the same name as a built-in one replaces it.
+	pub orderings: Vec<SampleOrder>,
+	/// How to handle files without an ordering indicator.
+	pub inference: InferenceSettings,
+	/// How data is laid out in the output workbooks.
+	pub output: OutputSettings,
+	/// How to convert areas from pixels to mm², if at all.
+	pub calibration: CalibrationSettings,
+	/// Which kernels to leave out of statistics. Area limits are in
+	/// pixels, as read from the csv, even when calibration is on.
+	pub exclusion: ExclusionRules,
+}//end struct Config
+
+/// Everything that can go wrong while loading a Config.
+#[derive(Debug)]
+pub enum ConfigError {
+	/// The file couldn't be read.
+	Io(io::Error),
+	/// The file isn't valid toml, or doesn't fit the layout of Config.
+	Parse(toml::de::Error),
+	/// The file was read fine, but some setting doesn't make sense.
+	Invalid(String),
+}//end enum ConfigError
+
+impl fmt::Display for ConfigError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			ConfigError::Io(err) => write!(f, "Couldn't read the config file: {}", err),
+			ConfigError::Parse(err) => write!(f, "Couldn't understand the config file: {}", err),
+			ConfigError::Invalid(msg) => write!(f, "Invalid setting in the config file: {}", msg),
+		}//end matching self
+	}//end fmt()
+}//end impl Display for ConfigError
+
+impl Error for ConfigError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			ConfigError::Io(err) => Some(err),
+			ConfigError::Parse(err) => Some(err),
+			ConfigError::Invalid(_) => None,
+		}//end matching self
+	}//end source()
+}//end impl Error for ConfigError
+
+impl Config {
+	/// Parses settings from the contents of a toml file, checking
+	/// that they make sense.
+	pub fn from_toml(text: &str) -> Result<Config, ConfigError> {
+		let mut config: Config = toml::from_str(text).map_err(ConfigError::Parse)?;
+		for ordering in config.orderings.iter() {
+			ordering.check().map_err(ConfigError::Invalid)?;
+		}//end checking each custom ordering
+		config.output.check().map_err(ConfigError::Invalid)?;
+		config.output.resolve_derived().map_err(ConfigError::Invalid)?;
+		config.calibration.check().map_err(ConfigError::Invalid)?;
+		config.exclusion.check().map_err(ConfigError::Invalid)?;
+		return Ok(config);
+	}//end from_toml()
+
+	/// Reads settings from the toml file at path.
+	pub fn load(path: &Path) -> Result<Config, ConfigError> {
+		let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+		return Config::from_toml(&text);
+	}//end load()
+
+	/// Looks for a file named CONFIG_FILE_NAME in the working
+	/// directory, and then next to the executable.
+	pub fn find() -> Option<PathBuf> {
+		let mut candidates = Vec::new();
+		if let Ok(dir) = env::current_dir() {candidates.push(dir.join(CONFIG_FILE_NAME));}
+		if let Ok(exe) = env::current_exe() {
+			if let Some(dir) = exe.parent() {candidates.push(dir.join(CONFIG_FILE_NAME));}
+		}//end if we know where the executable is
+		return candidates.into_iter().find(|path| path.is_file());
+	}//end find()
+
+	/// The set of orderings to match file ids against, with
+	/// custom orderings taking priority over built-in ones.
+	pub fn get_orderings(&self) -> Orderings {
+		Orderings::with_custom(&self.orderings)
+	}//end get_orderings()
+}//end impl for Config
@@ -1,158 +1,888 @@
-use std::{fs::{self}, path::PathBuf};
+use std::{error::Error, fmt, fs::{self}, io, path::PathBuf};
 
-#[derive(Clone, Copy,PartialEq, PartialOrd,Debug,Default)]
+use csv::StringRecord;
+use serde::Deserialize;
+
+use crate::infer::OrderingGuess;
+
+/// How the lines of a file are matched up with the labels of its
+/// sample ordering.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Alignment {
+	/// The nth line of the file gets the nth label, so a line
+	/// dropped by the scanner shifts every line after it.
+	#[default]
+	Position,
+	/// Each line gets the label at its grid index (starting at 1, or
+	/// at 0 for files numbered from 0),
+	/// so a dropped line just leaves its label without data.
+	GridIdx,
+}//end enum Alignment
+
+/// A single line of data from the csv file.  
+/// Measurements which were empty in the csv file are None.
+#[derive(Clone,PartialEq, PartialOrd,Debug,Default)]
 pub struct InputLine {
 	pub grid_idx: i32,
-	pub area1: i32,
-	pub area2: i32,
-	pub perc_area2: f32,
+	pub area1: Option<i32>,
+	pub area2: Option<i32>,
+	pub perc_area2: Option<f32>,
+	/// Any other numeric columns from the csv, like perimeter or
+	/// circularity, as header name and value, in column order.
+	pub extra: Vec<(String, Option<f32>)>,
+	/// If this line is left out of statistics, the reason why.
+	pub excluded: Option<String>,
 }
 
-#[derive(Clone, Copy, PartialEq, PartialOrd, Debug, Eq, Ord)]
-#[non_exhaustive]
-pub enum SampleOrder {
-	AB51,
-	BA15,
-	AB15,
-	BA51,
-	/// AB 1-10
-	AB110,
-	/// BA 10-1
-	BA101,
-	Unknown,
-}//end enum SampleOrder
+impl InputLine {
+	/// The value of the extra column with the given header name,
+	/// ignoring case, or None if it's missing or there's no such column.
+	pub fn get_extra(&self, name: &str) -> Option<f32> {
+		self.extra.iter()
+			.find(|(extra_name, _)| extra_name.eq_ignore_ascii_case(name))
+			.and_then(|(_, val)| *val)
+	}//end get_extra()
+}//end impl for InputLine
+
+/// Splits a sample label like 10b into its kernel number and
+/// section, so labels can be sorted into canonical order.  
+/// Returns None for labels which don't start with a number.
+/// 
+/// # Examples
+/// ```
+/// use milo_excel_helper::data::parse_label;
+/// assert_eq!(parse_label("10b"), Some((10, "b".to_string())));
+/// assert_eq!(parse_label("7"), Some((7, "".to_string())));
+/// assert_eq!(parse_label("??"), None);
+/// ```
+pub fn parse_label(label: &str) -> Option<(u32, String)> {
+	let label = label.trim();
+	let digits_end = label.find(|c: char| !c.is_ascii_digit()).unwrap_or(label.len());
+	let kernel = label[..digits_end].parse::<u32>().ok()?;
+	Some((kernel, label[digits_end..].to_lowercase()))
+}//end parse_label()
+
+/// Puts labels into canonical order (1a, 1b, 2a, 2b, etc),
+/// removing duplicates. Labels which can't be parsed by
+/// parse_label() are put last, in the order they were given.
+pub fn sort_labels(labels: Vec<String>) -> Vec<String> {
+	let mut labels = labels;
+	let mut seen = Vec::new();
+	labels.retain(|label| if seen.contains(label) {false} else {seen.push(label.clone()); true});
+	let (mut known, unknown): (Vec<String>, Vec<String>) = labels.into_iter()
+		.partition(|label| parse_label(label).is_some());
+	known.sort_by_key(|label| parse_label(label));
+	known.extend(unknown);
+	return known;
+}//end sort_labels()
+
+/// Describes how the samples in a file are laid out, so that we
+/// can label each line and put lines into canonical AB order
+/// (1a, 1b, 2a, 2b, etc).  
+/// Layouts can have any number of kernels, each with any number
+/// of sections, as long as labels are a kernel number followed by
+/// a section, like 3a or 12c.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize)]
+pub struct SampleOrder {
+	/// The name shown for this ordering, like AB51.
+	pub name: String,
+	/// Components of a file id which indicate a file uses this ordering.
+	#[serde(default)]
+	pub indicators: Vec<String>,
+	/// The sample label for each line of a file, in file order.
+	pub labels: Vec<String>,
+	/// For each position in canonical AB order, the index of the
+	/// line which belongs there. If empty, this is figured out by
+	/// sorting the labels. The line at each position of canonical
+	/// order is shown as that position's label in sorted order, so
+	/// the built-in AB51 and BA15 keep the rows they've always had.
+	#[serde(default)]
+	pub permutation: Vec<usize>,
+}//end struct SampleOrder
 
 impl SampleOrder {
-	pub fn from_file_id(file_id: &str) -> SampleOrder {
-		let file_components: Vec<&str> = file_id.split(&['-','.']).collect();
+	pub fn new(name: &str, indicators: &[&str], labels: &[&str], permutation: Vec<usize>) -> SampleOrder {
+		SampleOrder {
+			name: name.to_string(),
+			indicators: indicators.iter().map(|i| i.to_string()).collect(),
+			labels: labels.iter().map(|l| l.to_string()).collect(),
+			permutation,
+		}//end struct construction
+	}//end new()
 
-		let ab51_indic = vec!["up","uc","51ab","ab51","51AB","AB51"];
-		let ba15_indic = vec!["dn","dc","15ba","ba15","15BA","BA15"];
-		let ab15_indic = vec!["UP","ab15","15ab","AB15","15AB","top"];
-		let ba51_indic = vec!["DN","ba51","51ba","BA51","51BA","btm"];
-		let ab110_indic = vec!["ab110","AB110","ab1_10","AB1_10"];
-		let ba101_indic = vec!["ba101","BA101","ba10_1","BA10_1"];
-
-		let found_ab51 = file_components.iter().find(|c| ab51_indic.contains(c)).is_some();
-		let found_ba15 = file_components.iter().find(|c| ba15_indic.contains(c)).is_some();
-		let found_ab15 = file_components.iter().find(|c| ab15_indic.contains(c)).is_some();
-		let found_ba51 = file_components.iter().find(|c| ba51_indic.contains(c)).is_some();
-		let found_ab110 = file_components.iter().find(|c| ab110_indic.contains(c)).is_some();
-		let found_ba101 = file_components.iter().find(|c| ba101_indic.contains(c)).is_some();
-
-		if found_ab15 {SampleOrder::AB15}
-		else if found_ba51 {SampleOrder::BA51}
-		else if found_ab110 {SampleOrder::AB110}
-		else if found_ba101 {SampleOrder::BA101}
-		else if found_ab51 {SampleOrder::AB51}
-		else if found_ba15 {SampleOrder::BA15}
-		else {SampleOrder::Unknown}
-	}//end from_file_id
+	/// The ordering used for files we can't find any indicator for.
+	/// It has no labels, so lines are labelled by their position
+	/// in the file instead, like ?1, ?2, etc.
+	pub fn unknown() -> SampleOrder {
+		SampleOrder::new("Unknown", &[], &[], Vec::new())
+	}//end unknown()
+
+	/// The orderings we know about without any configuration,
+	/// in the order they're checked against file ids.
+	pub fn builtin() -> Vec<SampleOrder> {
+		vec![
+			SampleOrder::new("AB15", &["UP","ab15","15ab","AB15","15AB","top"],
+				&["1a","1b","2a","2b","3a","3b","4a","4b","5a","5b"],
+				(0..10).collect()),
+			SampleOrder::new("BA51", &["DN","ba51","51ba","BA51","51BA","btm"],
+				&["5b","5a","4b","4a","3b","3a","2b","2a","1b","1a"],
+				(0..10).rev().collect()),
+			SampleOrder::new("AB110", &["ab110","AB110","ab1_10","AB1_10"],
+				&["1a","1b","2a","2b","3a","3b","4a","4b","5a","5b","6a","6b","7a","7b","8a","8b","9a","9b","10a","10b"],
+				(0..20).collect()),
+			SampleOrder::new("BA101", &["ba101","BA101","ba10_1","BA10_1"],
+				&["10b","10a","9b","9a","8b","8a","7b","7a","6b","6a","5b","5a","4b","4a","3b","3a","2b","2a","1b","1a"],
+				(0..20).rev().collect()),
+			SampleOrder::new("AB51", &["up","uc","51ab","ab51","51AB","AB51"],
+				&["5a","5b","4a","4b","3a","3b","2a","2b","1a","1b"],
+				vec![1,0,3,2,5,4,7,6,9,8]),
+			SampleOrder::new("BA15", &["dn","dc","15ba","ba15","15BA","BA15"],
+				&["1b","1a","2b","2a","3b","3a","4b","4a","5b","5a"],
+				vec![8,9,6,7,4,5,2,3,0,1]),
+		]
+	}//end builtin()
+
+	/// Checks that this ordering makes sense, returning a
+	/// description of the problem if it doesn't.
+	pub fn check(&self) -> Result<(), String> {
+		if self.labels.is_empty() {return Err(format!("Ordering {} has no labels.", self.name));}
+		if sort_labels(self.labels.clone()).len() != self.labels.len() {
+			return Err(format!("Ordering {} uses the same label more than once.", self.name));
+		}//end if labels aren't unique
+		if self.permutation.is_empty() {return Ok(());}
+		if self.permutation.len() != self.labels.len() {
+			return Err(format!("Ordering {} has {} labels, but a permutation of length {}.", self.name, self.labels.len(), self.permutation.len()));
+		}//end if permutation and labels are different lengths
+		let mut sorted_perm = self.permutation.clone();
+		sorted_perm.sort_unstable();
+		if !sorted_perm.iter().enumerate().all(|(i, p)| i == *p) {
+			return Err(format!("The permutation for ordering {} should use each of 0 to {} exactly once.", self.name, self.labels.len() - 1));
+		}//end if permutation isn't actually a permutation
+		return Ok(());
+	}//end check()
+
+	/// For each position in canonical AB order, the index of the
+	/// label (and line) which belongs there.  
+	/// Uses permutation if one was given, and otherwise sorts the
+	/// labels with sort_labels().
+	pub fn canonical_permutation(&self) -> Vec<usize> {
+		if !self.permutation.is_empty() {return self.permutation.clone();}
+		let mut indices: Vec<usize> = (0..self.labels.len()).collect();
+		indices.sort_by_key(|idx| match parse_label(&self.labels[*idx]) {
+			Some(key) => (0, key),
+			None => (1, (0, String::new())),
+		});
+		return indices;
+	}//end canonical_permutation()
+
+	/// The labels of this ordering in canonical AB order, which
+	/// label the rows of chunks combining files.
+	pub fn canonical_labels(&self) -> Vec<String> {
+		sort_labels(self.labels.clone())
+	}//end canonical_labels()
+
+	/// The label for a position (0 for the first) in this ordering.
+	/// Positions beyond the labels of this ordering are labelled
+	/// by their number, like ?11, ?12, etc.
+	pub fn label_at(&self, position: i64) -> String {
+		match usize::try_from(position).ok().and_then(|idx| self.labels.get(idx)) {
+			Some(label) => label.clone(),
+			None => format!("?{}", position + 1),
+		}//end matching whether position has a label
+	}//end label_at()
 
 	pub fn get_labels(&self) -> Vec<&str> {
-		match self {
-			SampleOrder::AB51 => vec!["5a","5b","4a","4b","3a","3b","2a","2b","1a","1b"],
-			SampleOrder::BA15 => vec!["1b","1a","2b","2a","3b","3a","4b","4a","5b","5a"],
-			SampleOrder::AB15 => vec!["1a","1b","2a","2b","3a","3b","4a","4b","5a","5b"],
-			SampleOrder::BA51 => vec!["5b","5a","4b","4a","3b","3a","2b","2a","1b","1a"],
-			SampleOrder::AB110 => vec!["1a","1b","2a","2b","3a","3b","4a","4b","5a","5b","6a","6b","7a","7b","8a","8b","9a","9b","10a","10b"],
-			SampleOrder::BA101 => vec!["10b","10a","9b","9a","8b","8a","7b","7a","6b","6a","5b","5a","4b","4a","3b","3a","2b","2a","1b","1a"],
-			SampleOrder::Unknown => vec!["??","??","??","??","??","??","??","??","??","??"],
-		}//end matching self
+		self.labels.iter().map(|label| label.as_str()).collect()
 	}//end get_labels
 }//end impl for SampleOrder
 
-#[derive(Clone,PartialEq,PartialOrd,Debug)]
+/// The set of orderings which file ids are matched against.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Orderings {
+	/// Orderings in the order they're checked against file ids.
+	pub list: Vec<SampleOrder>,
+}//end struct Orderings
+
+impl Default for Orderings {
+	fn default() -> Orderings {Orderings {list: SampleOrder::builtin()}}
+}//end impl Default for Orderings
+
+impl Orderings {
+	/// Creates a set of orderings with custom orderings checked
+	/// first, followed by any built-in orderings which don't
+	/// share a name with a custom one.
+	pub fn with_custom(custom: &Vec<SampleOrder>) -> Orderings {
+		let mut list = custom.clone();
+		for builtin in SampleOrder::builtin() {
+			if !custom.iter().any(|order| order.name == builtin.name) {list.push(builtin);}
+		}//end adding built-in orderings which weren't replaced
+		Orderings {list}
+	}//end with_custom()
+
+	/// Matches the components of file_id, separated by dashes and
+	/// dots, against the indicators of every ordering.  
+	/// Components matching an indicator exactly are preferred, but
+	/// matches which only differ in case are recorded as well.
+	pub fn detect(&self, file_id: &str) -> OrderingDetection {
+		let file_components: Vec<&str> = file_id.split(&['-','.']).collect();
+		let mut matches = Vec::new();
+		for order in self.list.iter() {
+			let mut tokens = Vec::new();
+			let mut exact_case = false;
+			for component in file_components.iter() {
+				let exact = order.indicators.iter().any(|indic| indic.eq(component));
+				let loose = order.indicators.iter().any(|indic| indic.eq_ignore_ascii_case(component));
+				if exact || loose {
+					if !tokens.contains(&component.to_string()) {tokens.push(component.to_string());}
+					exact_case |= exact;
+				}//end if this component matched an indicator
+			}//end checking each component against this ordering
+			if tokens.len() > 0 {matches.push(OrderingMatch {ordering: order.clone(), tokens, exact_case});}
+		}//end checking each ordering
+		OrderingDetection {matches}
+	}//end detect()
+
+	/// Finds the ordering with the highest priority which has an
+	/// indicator matching a component of file_id, with components
+	/// separated by dashes and dots. Exact matches are preferred
+	/// over matches which ignore case.  
+	/// If none match, returns SampleOrder::unknown().
+	/// 
+	/// # Examples
+	/// ```
+	/// use milo_excel_helper::data::Orderings;
+	/// let orderings = Orderings::default();
+	/// assert_eq!(orderings.from_file_id("ns-ag05-131-ab15.tif").name, "AB15");
+	/// assert_eq!(orderings.from_file_id("ns-ag05-132-dn.tif").name, "BA15");
+	/// assert_eq!(orderings.from_file_id("ns-ag05-133.tif").name, "Unknown");
+	/// ```
+	pub fn from_file_id(&self, file_id: &str) -> SampleOrder {
+		self.detect(file_id).chosen()
+	}//end from_file_id
+}//end impl for Orderings
+
+/// One ordering whose indicators matched part of a file id.
+#[derive(Clone, PartialEq, Debug)]
+pub struct OrderingMatch {
+	pub ordering: SampleOrder,
+	/// The components of the file id which matched an indicator.
+	pub tokens: Vec<String>,
+	/// Whether at least one token matched an indicator exactly,
+	/// rather than only when ignoring case.
+	pub exact_case: bool,
+}//end struct OrderingMatch
+
+impl fmt::Display for OrderingMatch {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{} (from {})", self.ordering.name, self.tokens.join(", "))?;
+		if !self.exact_case {write!(f, " ignoring case")?;}
+		Ok(())
+	}//end fmt()
+}//end impl Display for OrderingMatch
+
+/// How confidently a file's ordering was found from its file id.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum OrderingStatus {
+	/// Exactly one ordering matched with the right case.
+	Matched,
+	/// No ordering matched with the right case, and exactly one
+	/// matched when ignoring case.
+	MatchedIgnoringCase,
+	/// More than one ordering matched with the right case, or more
+	/// than one matched ignoring case and none with the right case,
+	/// so the one with the highest priority was used.
+	Ambiguous,
+	/// No ordering matched.
+	Unknown,
+}//end enum OrderingStatus
+
+/// Every ordering which matched a file id, in priority order.
+/// 
+/// # Examples
+/// ```
+/// use milo_excel_helper::data::{Orderings, OrderingStatus};
+/// let orderings = Orderings::default();
+/// let detection = orderings.detect("ns-ag05-top-ba15.tif");
+/// assert_eq!(detection.status(), OrderingStatus::Ambiguous);
+/// assert_eq!(detection.matches.len(), 2);
+/// assert_eq!(detection.chosen().name, "AB15");
+/// 
+/// // Up matches both AB15's UP and AB51's up, but neither exactly
+/// let detection = orderings.detect("ns-ag05-Up.tif");
+/// assert_eq!(detection.status(), OrderingStatus::Ambiguous);
+/// assert!(detection.matches.iter().all(|m| !m.exact_case));
+/// 
+/// let detection = orderings.detect("ns-ag05-133-up.tif");
+/// assert_eq!(detection.status(), OrderingStatus::Matched);
+/// assert_eq!(detection.chosen().name, "AB51");
+/// 
+/// let detection = orderings.detect("ns-ag05-133-TOP.tif");
+/// assert_eq!(detection.status(), OrderingStatus::MatchedIgnoringCase);
+/// assert_eq!(detection.chosen().name, "AB15");
+/// ```
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct OrderingDetection {
+	pub matches: Vec<OrderingMatch>,
+}//end struct OrderingDetection
+
+impl OrderingDetection {
+	/// How confidently the ordering was found. A single match with
+	/// the right case wins over any matches which ignore case.
+	pub fn status(&self) -> OrderingStatus {
+		let exact = self.matches.iter().filter(|m| m.exact_case).count();
+		match (exact, self.matches.len()) {
+			(_, 0) => OrderingStatus::Unknown,
+			(1, _) => OrderingStatus::Matched,
+			(0, 1) => OrderingStatus::MatchedIgnoringCase,
+			_ => OrderingStatus::Ambiguous,
+		}//end matching number of exact and loose matches
+	}//end status()
+
+	/// The ordering with the highest priority among exact matches,
+	/// or among matches ignoring case if there are no exact ones.  
+	/// If nothing matched, returns SampleOrder::unknown().
+	pub fn chosen(&self) -> SampleOrder {
+		self.matches.iter()
+			.find(|m| m.exact_case)
+			.or(self.matches.first())
+			.map(|m| m.ordering.clone())
+			.unwrap_or_else(SampleOrder::unknown)
+	}//end chosen()
+}//end impl for OrderingDetection
+
+#[derive(Clone,PartialEq,Debug)]
 pub struct InputFile {
 	pub file_id: String,
 	pub input_lines: Vec<InputLine>,
 	pub sample_ordering: SampleOrder,
+	/// How sample_ordering was found from the file id.
+	pub ordering_detection: OrderingDetection,
+	/// If the file id had no ordering indicator, the ordering guessed
+	/// from the data. sample_ordering is different from the guess
+	/// if the user chose to override it.
+	pub inferred_ordering: Option<OrderingGuess>,
+	/// Lines which repeated the grid index of an earlier line for
+	/// this file id with different values. The earlier line is
+	/// kept in input_lines, and these are left out of the data.
+	pub conflicting_lines: Vec<InputLine>,
+	/// The number of rows which repeated an earlier line for this
+	/// file id exactly, and were left out, as when a file was
+	/// exported twice.
+	pub repeated_lines: usize,
 }//end struct InputFile
 
 impl InputFile {
-	pub fn new(file_id: &str, input_lines: Vec<InputLine>) -> InputFile {
-		InputFile {file_id: file_id.to_string(), input_lines, sample_ordering: SampleOrder::from_file_id(file_id)}
+	/// Creates a file using the ordering chosen by ordering_detection.
+	pub fn new(file_id: &str, input_lines: Vec<InputLine>, ordering_detection: OrderingDetection) -> InputFile {
+		InputFile {file_id: file_id.to_string(), input_lines, sample_ordering: ordering_detection.chosen(), ordering_detection, inferred_ordering: None, conflicting_lines: Vec::new(), repeated_lines: 0}
 	}//end new()
 
-	pub fn get_ab15_order(cur_order: SampleOrder, lines: &Vec<InputLine>) -> Vec<&InputLine> {
-		match cur_order {
-			SampleOrder::AB15 => return lines.iter().collect(),
-			SampleOrder::BA51 => return lines.iter().rev().collect(),
-			SampleOrder::AB51 | SampleOrder::BA15 => {
-				let mut ab_swp: Vec<&InputLine> = Vec::new();
-				let mut i = 0; let mut j = 1;
-				while i < lines.len() && j < lines.len() {
-					ab_swp.push(&lines[j]);
-					ab_swp.push(&lines[i]);
-				i+= 2; j += 2;}
-				// do iterator stuff to return stuff in the right format
-				let ab_swp_iter = ab_swp.iter().map(|n| *n);
-				if cur_order == SampleOrder::BA15 {return ab_swp_iter.rev().collect();}
-				else {return ab_swp_iter.collect()}
-			},
-			SampleOrder::AB110 => return lines.iter().collect(),
-			SampleOrder::BA101 => return lines.iter().rev().collect(),
-			SampleOrder::Unknown => return lines.iter().collect(),
-		}
-	}
+	/// Puts lines into canonical AB order (1a, 1b, 2a, 2b, etc),
+	/// using the canonical permutation of cur_order.  
+	/// Lines beyond the labels of cur_order are put after the
+	/// rest, in file order.
+	pub fn get_ab15_order<'a>(cur_order: &SampleOrder, lines: &'a Vec<InputLine>) -> Vec<&'a InputLine> {
+		let permutation = cur_order.canonical_permutation();
+		let mut ordered: Vec<&InputLine> = permutation.iter()
+			.filter_map(|line_idx| lines.get(*line_idx))
+			.collect();
+		// add anything the permutation didn't cover
+		for (line_idx, line) in lines.iter().enumerate() {
+			if !permutation.contains(&line_idx) {ordered.push(line);}
+		}//end adding lines not in the permutation
+		return ordered;
+	}//end get_ab15_order()
+
+	/// The position (0 for the first) of each line within the labels
+	/// of this file's ordering, in file order.  
+	/// Grid indices count from 1, unless the file has smaller ones,
+	/// as when the scanner numbers from 0. A file counting from 1
+	/// with its first line dropped still counts from 1.
+	fn get_positions(&self, alignment: Alignment) -> Vec<i64> {
+		let base = self.input_lines.iter().map(|line| line.grid_idx as i64).min().unwrap_or(1).min(1);
+		self.input_lines.iter().enumerate()
+			.map(|(line_idx, line)| match alignment {
+				Alignment::Position => line_idx as i64,
+				Alignment::GridIdx => line.grid_idx as i64 - base,
+			})
+			.collect()
+	}//end get_positions()
+
+	/// The label of each line, in file order.
+	pub fn get_labels(&self, alignment: Alignment) -> Vec<String> {
+		self.get_positions(alignment).into_iter()
+			.map(|position| self.sample_ordering.label_at(position))
+			.collect()
+	}//end get_labels()
+
+	/// Each line paired with its label, in canonical AB order.  
+	/// Lines beyond the labels of the ordering are put after the
+	/// rest, in file order.
+	/// 
+	/// # Examples
+	/// ```
+	/// use milo_excel_helper::data::{Alignment, InputFile, InputLine, Orderings};
+	/// let lines: Vec<InputLine> = [1, 2, 4].iter()
+	/// 	.map(|grid_idx| InputLine {grid_idx: *grid_idx, ..Default::default()})
+	/// 	.collect();
+	/// let file = InputFile::new("ns-ag05-ab15.tif", lines, Orderings::default().detect("ns-ag05-ab15.tif"));
+	/// let labels = |alignment| file.get_labelled_lines(alignment).into_iter()
+	/// 	.map(|(label, line)| (label, line.grid_idx))
+	/// 	.collect::<Vec<_>>();
+	/// assert_eq!(labels(Alignment::Position)[2], ("2a".to_string(), 4));
+	/// assert_eq!(labels(Alignment::GridIdx)[2], ("2b".to_string(), 4));
+	/// 
+	/// // a file numbered from 0 lines up the same way
+	/// let lines: Vec<InputLine> = [0, 1, 3].iter()
+	/// 	.map(|grid_idx| InputLine {grid_idx: *grid_idx, ..Default::default()})
+	/// 	.collect();
+	/// let file = InputFile::new("ns-ag05-ab15.tif", lines, Orderings::default().detect("ns-ag05-ab15.tif"));
+	/// let labels: Vec<(String, i32)> = file.get_labelled_lines(Alignment::GridIdx).into_iter()
+	/// 	.map(|(label, line)| (label, line.grid_idx))
+	/// 	.collect();
+	/// assert_eq!(labels, vec![("1a".to_string(), 0), ("1b".to_string(), 1), ("2b".to_string(), 3)]);
+	/// 
+	/// // the built-in AB51 puts the second line of the file first
+	/// let lines: Vec<InputLine> = (1..=10).map(|grid_idx| InputLine {grid_idx, ..Default::default()}).collect();
+	/// let file = InputFile::new("ns-ag05-ab51.tif", lines, Orderings::default().detect("ns-ag05-ab51.tif"));
+	/// let grid_idxs: Vec<i32> = file.get_labelled_lines(Alignment::Position).iter().map(|(_, line)| line.grid_idx).collect();
+	/// assert_eq!(grid_idxs, vec![2, 1, 4, 3, 6, 5, 8, 7, 10, 9]);
+	/// assert_eq!(file.get_labelled_lines(Alignment::Position)[0].0, "1a");
+	/// ```
+	pub fn get_labelled_lines(&self, alignment: Alignment) -> Vec<(String, &InputLine)> {
+		let permutation = self.sample_ordering.canonical_permutation();
+		let canonical_labels = self.sample_ordering.canonical_labels();
+		let mut labelled: Vec<(usize, String, &InputLine)> = self.get_positions(alignment).into_iter()
+			.zip(self.input_lines.iter())
+			.map(|(position, line)| {
+				match permutation.iter().position(|p| *p as i64 == position) {
+					Some(rank) => (rank, canonical_labels.get(rank).cloned().unwrap_or(self.sample_ordering.label_at(position)), line),
+					None => (usize::MAX, self.sample_ordering.label_at(position), line),
+				}//end matching whether this line has a place in canonical order
+			})
+			.collect();
+		// stable sort, so anything the permutation didn't cover stays in file order
+		labelled.sort_by_key(|(rank, _, _)| *rank);
+		return labelled.into_iter().map(|(_, label, line)| (label, line)).collect();
+	}//end get_labelled_lines()
+
+	/// The number of lines this file's ordering expects it to have,
+	/// or None if the ordering is unknown.
+	pub fn expected_lines(&self) -> Option<usize> {
+		match self.sample_ordering.labels.len() {
+			0 => None,
+			n => Some(n),
+		}//end matching number of labels
+	}//end expected_lines()
 }//end impl for InputFile
 
-pub fn read_csv_file(file: &PathBuf) -> Option<Vec<InputFile>> {
-	let mut input_files: Vec<InputFile> = Vec::new();
-	let mut last_file_id;
-	let mut tmp_row_data = Vec::new();
+/// Information about how a csv file was scanned, read from the
+/// lines before its header row, like the software version,
+/// thresholds or the date.  
+/// Entries are kept in the order they appear in the file.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct RunMetadata {
+	/// Each setting name, paired with its value.
+	pub entries: Vec<(String, String)>,
+}//end struct RunMetadata
 
-	let mut _headers: Vec<&str> = Vec::new();
-	let mut header_idx = 0;
+impl RunMetadata {
+	/// Reads metadata from the rows before the header row.  
+	/// The first non-empty cell of each row is the setting name, and
+	/// the rest of the row is its value. A row with just one cell
+	/// like Date: 2024-03-01 is split at the first colon or equals sign.
+	/// Repeated names are numbered, like Threshold (2).
+	/// 
+	/// # Examples
+	/// ```
+	/// use milo_excel_helper::data::RunMetadata;
+	/// let metadata = RunMetadata::from_rows(&[
+	/// 	vec!["Milo Scan", "v2.1"],
+	/// 	vec!["Threshold", "120", ""],
+	/// 	vec!["Date: 2024-03-01"],
+	/// 	vec!["", ""],
+	/// ]);
+	/// assert_eq!(metadata.get("Milo Scan"), Some("v2.1"));
+	/// assert_eq!(metadata.get("Threshold"), Some("120"));
+	/// assert_eq!(metadata.get("Date"), Some("2024-03-01"));
+	/// assert_eq!(metadata.entries.len(), 3);
+	/// ```
+	pub fn from_rows(rows: &[Vec<&str>]) -> RunMetadata {
+		let mut metadata = RunMetadata::default();
+		for row in rows {
+			let cells: Vec<&str> = row.iter()
+				.map(|cell| cell.trim())
+				.skip_while(|cell| cell.is_empty())
+				.collect();
+			let Some(first) = cells.first() else {continue;};
+			let rest: Vec<&str> = cells[1..].iter().cloned().filter(|cell| !cell.is_empty()).collect();
+			let (name, value) = match (rest.is_empty(), first.split_once([':', '='])) {
+				(true, Some((name, value))) => (name.trim().to_string(), value.trim().to_string()),
+				_ => (first.to_string(), rest.join(", ")),
+			};//end matching whether the value is in the same cell as the name
+			let mut unique_name = name.clone();
+			let mut count = 1;
+			while metadata.get(&unique_name).is_some() {
+				count += 1;
+				unique_name = format!("{} ({})", name, count);
+			}//end numbering repeated names
+			metadata.entries.push((unique_name, value));
+		}//end reading each row
+		return metadata;
+	}//end from_rows()
 
-	let contents = fs::read_to_string(file).unwrap();
-	let lines: Vec<&str> = contents.split('\n').collect();
-	
-	// get the headers and header_idx
-	loop {
-		let line = lines[header_idx];
-		let cols: Vec<&str> = line.split(',').filter(|col| !col.eq(&"")).collect();
-		if cols.len() > 5 {
-			// save headers
-			_headers = cols;
-			// figure out the first file_id
-			let n_line = lines[header_idx + 1];
-			let n_cols: Vec<&str> = n_line.split(',').collect();
-			last_file_id = n_cols[0].to_string();
-			// exit loop, work here is done
-			break;
-		} else {header_idx += 1;}
+	/// The value of the setting with the given name, if there is one.
+	pub fn get(&self, name: &str) -> Option<&str> {
+		self.entries.iter()
+			.find(|(entry_name, _)| entry_name == name)
+			.map(|(_, value)| value.as_str())
+	}//end get()
+}//end impl for RunMetadata
+
+/// Everything read from a csv file.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Dataset {
+	/// The data for each file id, in the order they first appear.
+	pub files: Vec<InputFile>,
+	/// Information about the scan, from before the header row.
+	pub metadata: RunMetadata,
+}//end struct Dataset
+
+/// Names which might be used in the header row for each of the
+/// columns we read into an InputLine.  
+/// When matching against a header row, case and surrounding
+/// whitespace are ignored.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[serde(default)]
+pub struct ColumnAliases {
+	pub file_id: Vec<String>,
+	pub grid_idx: Vec<String>,
+	pub area1: Vec<String>,
+	pub area2: Vec<String>,
+	pub perc_area2: Vec<String>,
+}//end struct ColumnAliases
+
+impl Default for ColumnAliases {
+	fn default() -> ColumnAliases {
+		fn strs(names: &[&str]) -> Vec<String> {names.iter().map(|n| n.to_string()).collect()}
+		ColumnAliases {
+			file_id: strs(&["FileID","File ID","File_ID","Filename","File"]),
+			grid_idx: strs(&["GridIdx","Grid Idx","Grid_Idx","GridIndex","Grid Index"]),
+			area1: strs(&["Area1","Area 1","Area_1","KernelArea","Kernel Area"]),
+			area2: strs(&["Area2","Area 2","Area_2","EndospermArea","Endosperm Area"]),
+			perc_area2: strs(&["%Area2","% Area2","%Area 2","% Area 2","PercArea2","Area2%"]),
+		}//end struct construction
+	}//end default()
+}//end impl Default for ColumnAliases
+
+/// The index of each column we care about within a row of
+/// the csv file, as determined from the header row.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ColumnMap {
+	pub file_id: usize,
+	pub grid_idx: usize,
+	pub area1: usize,
+	pub area2: usize,
+	pub perc_area2: usize,
+	/// Every other column with a header name, which might hold an
+	/// extra measurement, as column index and trimmed header name.
+	pub extra: Vec<(usize, String)>,
+}//end struct ColumnMap
+
+impl ColumnMap {
+	/// Attempts to find every required column within headers,
+	/// using aliases to decide which header names are acceptable.  
+	/// If any required columns can't be found, returns the names
+	/// of the missing columns instead.
+	/// 
+	/// # Examples
+	/// ```
+	/// use milo_excel_helper::data::{ColumnAliases, ColumnMap};
+	/// let headers = vec!["GridIdx","FileID","%Area2","Area2","Area1","Perimeter"];
+	/// let map = ColumnMap::from_headers(&headers, &ColumnAliases::default()).unwrap();
+	/// assert_eq!(map.file_id, 1);
+	/// assert_eq!(map.area1, 4);
+	/// assert_eq!(map.perc_area2, 2);
+	/// assert_eq!(map.extra, vec![(5, "Perimeter".to_string())]);
+	/// 
+	/// let headers = vec!["FileID","GridIdx","Area1","%Area2"];
+	/// let missing = ColumnMap::from_headers(&headers, &ColumnAliases::default()).unwrap_err();
+	/// assert_eq!(missing, vec!["Area2".to_string()]);
+	/// ```
+	pub fn from_headers(headers: &Vec<&str>, aliases: &ColumnAliases) -> Result<ColumnMap, Vec<String>> {
+		let find_col = |names: &Vec<String>| -> Option<usize> {
+			headers.iter().position(|header| {
+				names.iter().any(|name| name.trim().eq_ignore_ascii_case(header.trim()))
+			})
+		};//end closure find_col
+
+		let file_id = find_col(&aliases.file_id);
+		let grid_idx = find_col(&aliases.grid_idx);
+		let area1 = find_col(&aliases.area1);
+		let area2 = find_col(&aliases.area2);
+		let perc_area2 = find_col(&aliases.perc_area2);
+
+		match (file_id, grid_idx, area1, area2, perc_area2) {
+			(Some(file_id), Some(grid_idx), Some(area1), Some(area2), Some(perc_area2)) => {
+				let required = [file_id, grid_idx, area1, area2, perc_area2];
+				let extra = headers.iter().enumerate()
+					.filter(|(col_idx, header)| !required.contains(col_idx) && !header.trim().is_empty())
+					.map(|(col_idx, header)| (col_idx, header.trim().to_string()))
+					.collect();
+				Ok(ColumnMap {file_id, grid_idx, area1, area2, perc_area2, extra})
+			},
+			_ => {
+				let mut missing = Vec::new();
+				if file_id.is_none() {missing.push("FileID".to_string());}
+				if grid_idx.is_none() {missing.push("GridIdx".to_string());}
+				if area1.is_none() {missing.push("Area1".to_string());}
+				if area2.is_none() {missing.push("Area2".to_string());}
+				if perc_area2.is_none() {missing.push("%Area2".to_string());}
+				Err(missing)
+			}//end case that we're missing at least one column
+		}//end matching whether we found every column
+	}//end from_headers()
+
+	/// The number of columns a row needs to have in order to
+	/// contain every column in this map.
+	pub fn min_cols(&self) -> usize {
+		[self.file_id, self.grid_idx, self.area1, self.area2, self.perc_area2]
+			.iter().max().expect("array is not empty") + 1
+	}//end min_cols()
+}//end impl for ColumnMap
+
+/// Everything that can go wrong while reading a csv file
+/// with read_csv_file().  
+/// Line and column numbers are 1-based, to match what you'd
+/// see opening the file in a text editor or Excel.
+#[derive(Debug)]
+pub enum CsvError {
+	/// The file couldn't be read at all.
+	Io(io::Error),
+	/// The contents of the file couldn't be split into rows and cells.
+	Parse(csv::Error),
+	/// No row in the file contained every required column.
+	/// Holds the names of the columns missing from the row
+	/// which came closest, or nothing if no row looked like a header.
+	NoHeader(Vec<String>),
+	/// A row after the header didn't have enough columns.
+	MalformedRow {line: usize, found_cols: usize, expected_cols: usize},
+	/// A cell which should hold a number couldn't be parsed as one.
+	NonNumeric {line: usize, column: usize, header: String, value: String},
+}//end enum CsvError
+
+impl fmt::Display for CsvError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			CsvError::Io(err) => write!(f, "Couldn't read the file: {}", err),
+			CsvError::Parse(err) => write!(f, "Couldn't split the file into rows and cells: {}", err),
+			CsvError::NoHeader(missing) if missing.is_empty() => write!(f, "Couldn't find a header row."),
+			CsvError::NoHeader(missing) => write!(f, "Couldn't find a header row with the required column(s) {}.", missing.join(", ")),
+			CsvError::MalformedRow {line, found_cols, expected_cols} => write!(f, "Line {} has {} column(s), but at least {} are needed.", line, found_cols, expected_cols),
+			CsvError::NonNumeric {line, column, header, value} => write!(f, "Line {}, column {} ({}): \"{}\" is not a number.", line, column, header, value),
+		}//end matching self
+	}//end fmt()
+}//end impl Display for CsvError
+
+impl Error for CsvError {
+	fn source(&self) -> Option<&(dyn Error + 'static)> {
+		match self {
+			CsvError::Io(err) => Some(err),
+			CsvError::Parse(err) => Some(err),
+			_ => None,
+		}//end matching self
+	}//end source()
+}//end impl Error for CsvError
+
+impl From<io::Error> for CsvError {
+	fn from(err: io::Error) -> CsvError {CsvError::Io(err)}
+}//end impl From<io::Error> for CsvError
+
+/// The delimiters we'll try, in order, when figuring out how
+/// the cells of a file are separated.
+const DELIMITERS: [u8; 3] = [b',', b';', b'\t'];
+
+/// Splits the contents of a delimited text file into rows of
+/// cells, following RFC 4180 quoting rules. Each row is paired
+/// with the line number it starts on.  
+/// Line endings may be LF or CRLF, and rows don't all need to
+/// have the same number of cells.
+fn tokenize(contents: &str, delimiter: u8) -> Result<Vec<(usize, StringRecord)>, CsvError> {
+	let mut reader = csv::ReaderBuilder::new()
+		.has_headers(false)
+		.flexible(true)
+		.delimiter(delimiter)
+		.from_reader(contents.as_bytes());
+	let mut rows = Vec::new();
+	for record in reader.records() {
+		let record = record.map_err(CsvError::Parse)?;
+		let line = record.position().map_or(rows.len() + 1, |pos| pos.line() as usize);
+		rows.push((line, record));
+	}//end reading each record
+	return Ok(rows);
+}//end tokenize()
+
+/// Finds the first row which looks like a header and contains
+/// every required column.  
+/// On success, returns the index of the header within rows and
+/// the column map it gives. Otherwise, returns the missing columns
+/// of the header-like row which came closest.
+fn find_header(rows: &Vec<(usize, StringRecord)>, aliases: &ColumnAliases) -> Result<(usize, ColumnMap), Vec<String>> {
+	let mut closest_missing: Vec<String> = Vec::new();
+	for (row_idx, (_, row)) in rows.iter().enumerate() {
+		let cols: Vec<&str> = row.iter().collect();
+		if cols.iter().filter(|col| !col.trim().is_empty()).count() < 5 {continue;}
+		match ColumnMap::from_headers(&cols, aliases) {
+			Ok(map) => return Ok((row_idx, map)),
+			Err(missing) => {
+				if closest_missing.is_empty() || missing.len() < closest_missing.len() {closest_missing = missing;}
+			}
+		}//end matching whether this row has all the columns we need
 	}//end looping to find headers
+	return Err(closest_missing);
+}//end find_header()
 
-	// loop over lines after headers, get the data
-	for i in (header_idx+1)..lines.len() {
-		// just get the actual columns
-		let line = lines[i];
-		let cols: Vec<&str> = line.split(',').collect();
-		if cols.len() < 5 {if !cols.eq(&(vec![""])) {println!("{:?}",cols)}; continue;}
-		// get all the actual data
-		let file_id = cols[0];
-		let grid_idx: i32 = cols[1].parse().unwrap_or(-2);
-		let area1: i32 = cols[2].parse().unwrap_or(-2.) as i32;
-		let area2: i32 = cols[3].parse().unwrap_or(-2.) as i32;
-		let perc_area2: f32 = cols[4].parse().unwrap_or(-2.);
-		let new_input_line = InputLine {grid_idx,area1,area2,perc_area2,};
-		// make sure we're separating files from lines
-		if !file_id.eq(&last_file_id) {
-			let new_input_file = InputFile::new(&last_file_id, tmp_row_data);
-			input_files.push(new_input_file);
-			tmp_row_data = vec![new_input_line];
-			last_file_id = file_id.to_string();
-		} else {
-			tmp_row_data.push(new_input_line);
-		}//end else we add a new line as usual
-	}//end looping over indices for lines
+/// Reads the csv file at the given path.  
+/// Cells may be separated by commas, semicolons or tabs, which
+/// is figured out by finding which delimiter gives a header row.
+/// When cells aren't separated by commas, numbers may use a
+/// decimal comma, as European-locale Excel saves them.  
+/// The header row is used to figure out which column holds
+/// which value, with aliases deciding which names are accepted
+/// for each column.  
+/// The sample ordering of each file is decided by matching its
+/// file id against orderings.  
+/// Any other column whose cells are all numbers (or empty) is read
+/// into the extra values of each line, named by its header.  
+/// Rows before the header are read as metadata about the scan.  
+/// Rows are grouped into files by file id, even when rows for the
+/// same file id aren't next to each other. A row repeating the grid
+/// index of an earlier row for its file id is dropped, and kept in
+/// the file's conflicting_lines if its values are different, or
+/// counted in its repeated_lines if they're the same.  
+/// Empty measurement cells are read as missing values, and areas
+/// are rounded to whole pixels. Any other cell which can't be parsed
+/// as a number is reported as an error, as are GridIdx cells which
+/// are empty or not whole numbers, and rows after the header which
+/// don't have enough columns.
+pub fn read_csv_file(file: &PathBuf, aliases: &ColumnAliases, orderings: &Orderings) -> Result<Dataset, CsvError> {
+	// lines, conflicting lines and the number of repeated lines for each file id, in order of first appearance
+	let mut lines_by_id: Vec<(String, Vec<InputLine>, Vec<InputLine>, usize)> = Vec::new();
+
+	let contents = fs::read_to_string(file)?;
+	let contents = contents.trim_start_matches('\u{feff}');
 	
-	// clean up anything left and add to returned Vec
-	if tmp_row_data.len() > 0 {
-		let new_input_file = InputFile::new(&last_file_id,tmp_row_data);
-		input_files.push(new_input_file);
-	}//end if we should add the last few lines to input_files
+	// figure out the delimiter, the header row, and which column is which
+	let mut found = None;
+	let mut closest_missing: Vec<String> = Vec::new();
+	// a delimiter which can't split the file just isn't the right one, unless none can
+	let mut parse_err = None;
+	let mut tokenized_any = false;
+	for delimiter in DELIMITERS {
+		let rows = match tokenize(contents, delimiter) {
+			Ok(rows) => rows,
+			Err(err) => {parse_err.get_or_insert(err); continue;},
+		};//end matching whether this delimiter could split the file
+		tokenized_any = true;
+		match find_header(&rows, aliases) {
+			Ok((header_idx, col_map)) => {found = Some((delimiter, rows, header_idx, col_map)); break;},
+			Err(missing) => {
+				if closest_missing.is_empty() || (!missing.is_empty() && missing.len() < closest_missing.len()) {closest_missing = missing;}
+			}
+		}//end matching whether we found a header with this delimiter
+	}//end trying each delimiter
+	let (delimiter, rows, header_idx, col_map) = match (found, parse_err) {
+		(Some(found), _) => found,
+		(None, Some(err)) if !tokenized_any => return Err(err),
+		(None, _) => return Err(CsvError::NoHeader(closest_missing)),
+	};//end matching whether any delimiter worked
+	let decimal_comma = delimiter != b',';
+	let headers = rows[header_idx].1.clone();
+
+	// parses a number from a cell, treating empty cells as missing
+	let parse_cell = |cols: &StringRecord, col_idx: usize, line: usize| -> Result<Option<f32>, CsvError> {
+		let value = cols[col_idx].trim();
+		if value.is_empty() {return Ok(None);}
+		let parsed = match decimal_comma && !value.contains('.') {
+			true => value.replace(',', ".").parse::<f32>(),
+			false => value.parse::<f32>(),
+		};//end parsing with the right decimal separator
+		parsed.map(Some).map_err(|_| CsvError::NonNumeric {
+			line,
+			column: col_idx + 1,
+			header: headers.get(col_idx).unwrap_or("").to_string(),
+			value: value.to_string(),
+		})
+	};//end closure parse_cell
+
+	// only keep extra columns where every cell is a number, or empty
+	let extra_cols: Vec<&(usize, String)> = col_map.extra.iter()
+		.filter(|(col_idx, _)| rows.iter().skip(header_idx + 1)
+			.filter(|(_, cols)| cols.len() > *col_idx)
+			.all(|(line, cols)| parse_cell(cols, *col_idx, *line).is_ok()))
+		.collect();
+
+	// loop over rows after headers, get the data
+	for (line, cols) in rows.iter().skip(header_idx + 1) {
+		let line = *line;
+		if cols.iter().all(|col| col.trim().is_empty()) {continue;}
+		if cols.len() < col_map.min_cols() {
+			return Err(CsvError::MalformedRow {line, found_cols: cols.len(), expected_cols: col_map.min_cols()});
+		}//end if this row is too short to have all our data
+		// get all the actual data
+		let file_id = cols[col_map.file_id].trim();
+		let grid_idx = parse_cell(cols, col_map.grid_idx, line)?
+			.filter(|grid_idx| grid_idx.fract() == 0.)
+			.ok_or(CsvError::NonNumeric {
+				line,
+				column: col_map.grid_idx + 1,
+				header: headers.get(col_map.grid_idx).unwrap_or("").to_string(),
+				value: cols[col_map.grid_idx].trim().to_string(),
+			})? as i32;
+		let area1 = parse_cell(cols, col_map.area1, line)?.map(|area| area.round() as i32);
+		let area2 = parse_cell(cols, col_map.area2, line)?.map(|area| area.round() as i32);
+		let perc_area2 = parse_cell(cols, col_map.perc_area2, line)?;
+		let extra = extra_cols.iter()
+			.map(|(col_idx, name)| {
+				let val = match cols.len() > *col_idx {
+					true => parse_cell(cols, *col_idx, line)?,
+					false => None,
+				};//end matching whether this row reaches this column
+				Ok((name.clone(), val))
+			})
+			.collect::<Result<Vec<(String, Option<f32>)>, CsvError>>()?;
+		let new_input_line = InputLine {grid_idx,area1,area2,perc_area2,extra,excluded: None};
+		// group lines by file id, even if rows for a file id aren't next to each other
+		let (lines, conflicts, repeats) = match lines_by_id.iter_mut().find(|(id, _, _, _)| id == file_id) {
+			Some((_, lines, conflicts, repeats)) => (lines, conflicts, repeats),
+			None => {
+				lines_by_id.push((file_id.to_string(), Vec::new(), Vec::new(), 0));
+				let (_, lines, conflicts, repeats) = lines_by_id.last_mut().unwrap();
+				(lines, conflicts, repeats)
+			}
+		};//end matching whether we've seen this file id before
+		match lines.iter().find(|line| line.grid_idx == grid_idx) {
+			None => lines.push(new_input_line),
+			Some(earlier) if *earlier == new_input_line => *repeats += 1,
+			Some(_) => conflicts.push(new_input_line),
+		}//end matching whether this grid index was already read for this file id
+	}//end looping over rows after the header
+
+	let files = lines_by_id.into_iter()
+		.map(|(file_id, lines, conflicts, repeats)| {
+			let mut input_file = InputFile::new(&file_id, lines, orderings.detect(&file_id));
+			input_file.conflicting_lines = conflicts;
+			input_file.repeated_lines = repeats;
+			input_file
+		})
+		.collect();
+
+	// anything before the header should be information about the scan
+	let preamble: Vec<Vec<&str>> = rows[..header_idx].iter()
+		.map(|(_, cols)| cols.iter().collect())
+		.collect();
+	let metadata = RunMetadata::from_rows(&preamble);
 
-	return Some(input_files);
+	return Ok(Dataset {files, metadata});
 }//end read_csv_file(reader)
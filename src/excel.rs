@@ -1,27 +1,455 @@
-use std::{ops::Sub, path::PathBuf, slice::Iter};
+use std::{path::PathBuf, slice::Iter};
 
-use rust_xlsxwriter::{Format, FormatAlign, Workbook, XlsxError};
+use rust_xlsxwriter::{column_number_to_name, Chart, ChartErrorBars, ChartErrorBarsType, ChartRange, Color, ConditionalFormat3ColorScale, ConditionalFormatFormula, DocProperties, Format, FormatAlign, FormatBorder, Formula, Table, TableColumn, Workbook, XlsxError};
+use serde::Deserialize;
 
-use crate::data::{InputFile, InputLine, SampleOrder};
+use crate::{calibration::{CalibratedReport, Calibration}, data::{parse_label, sort_labels, Alignment, InputFile, InputLine, RunMetadata, SampleOrder}, expr::DerivedMetric, math::{self, robust_summarize, summarize, SdEstimator, Stat}, outlier::OutlierSettings, validate::Finding};
+
+/// Settings for how data is laid out in the output workbooks.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[serde(default)]
+pub struct OutputSettings {
+	/// How lines from different files are lined up with each other.
+	pub alignment: Alignment,
+	/// The values to make sum and stats sheets for, in order.
+	/// Extra columns not found in the data are skipped.
+	pub metrics: Vec<OutputVal>,
+	/// Values computed from other values of each line, which can
+	/// be added to metrics by name.
+	pub derived: Vec<DerivedMetric>,
+	/// How standard deviations (and so CVs) are calculated.
+	pub sd_estimator: SdEstimator,
+	/// Whether the sum and stats sheets also show the median, IQR,
+	/// MAD and trimmed mean, which are less thrown off by a single
+	/// bad kernel than Avg, Std and CV.
+	pub robust: bool,
+	/// The fraction of values left out for the trimmed mean, half
+	/// from each end, like the percent given to TRIMMEAN in Excel.
+	pub trim_fraction: f64,
+	/// How values far off from the other scans of a sample are
+	/// found, to be highlighted on the sum sheet.
+	pub outliers: OutlierSettings,
+	/// Which sheets get native Excel charts.
+	pub charts: ChartSettings,
+	/// Limits which values in a column should stay within, with
+	/// values outside them shown in red or amber.
+	pub thresholds: Vec<ThresholdRule>,
+	/// Whether %Area2 on the sorted sheets gets a three color scale,
+	/// to show it like a heatmap.
+	pub heatmap: bool,
+	/// Whether each chunk is written as an Excel Table, with a filter
+	/// and banded rows, named after its sheet and title.
+	pub tables: bool,
+	/// Whether the chunks on the sum and stats sheets get workbook-level
+	/// names, so other workbooks and queries can refer to them.
+	pub named_ranges: bool,
+	/// Whether chunks are stacked down each sheet or placed side by side.
+	pub layout: ChunkLayout,
+	/// Whether chunks with a title get a caption row above them.
+	pub captions: bool,
+	/// Whether the header row and label column stay in view when scrolling.
+	pub freeze_panes: bool,
+	/// Whether column widths are fit to their contents.
+	pub autofit: bool,
+	/// The conversion from pixels to mm² for the csv being output.
+	/// This is set for each csv from the calibration settings, rather
+	/// than read from the config file.
+	#[serde(skip)]
+	pub calibration: Option<Calibration>,
+}//end struct OutputSettings
+
+impl Default for OutputSettings {
+	fn default() -> OutputSettings {
+		OutputSettings {
+			alignment: Alignment::default(),
+			metrics: vec![OutputVal::KernelArea, OutputVal::EndospermArea, OutputVal::PercentArea],
+			derived: Vec::new(),
+			sd_estimator: SdEstimator::default(),
+			robust: false,
+			trim_fraction: 0.2,
+			outliers: OutlierSettings::default(),
+			charts: ChartSettings::default(),
+			thresholds: Vec::new(),
+			heatmap: false,
+			tables: false,
+			named_ranges: false,
+			layout: ChunkLayout::default(),
+			captions: false,
+			freeze_panes: false,
+			autofit: false,
+			calibration: None,
+		}
+	}//end default()
+}//end impl Default for OutputSettings
+
+impl OutputSettings {
+	/// Checks that the trim fraction is from 0 up to, but not
+	/// including, 1, and that the outlier settings and thresholds
+	/// make sense.
+	pub fn check(&self) -> Result<(), String> {
+		if !(0. ..1.).contains(&self.trim_fraction) {
+			return Err(format!("Output trim_fraction ({}) must be at least 0 and less than 1", self.trim_fraction));
+		}//end if trim fraction is out of range
+		for rule in self.thresholds.iter() {rule.check()?;}
+		return self.outliers.check();
+	}//end check()
+
+	/// Turns any metric named after a derived metric into that
+	/// derived metric, since metrics are read by name alone.  
+	/// Fails if a derived metric shares its name with a built-in
+	/// column or another derived metric.
+	pub fn resolve_derived(&mut self) -> Result<(), String> {
+		for (idx, metric) in self.derived.iter().enumerate() {
+			if !matches!(OutputVal::from(metric.name.clone()), OutputVal::Extra(_)) {
+				return Err(format!("Derived metric {} has the same name as a built-in column", metric.name));
+			}//end if name is taken by a built-in column
+			if self.derived[..idx].iter().any(|other| other.name.eq_ignore_ascii_case(&metric.name)) {
+				return Err(format!("More than one derived metric is named {}", metric.name));
+			}//end if name is used twice
+		}//end checking each derived metric
+		for metric in self.metrics.iter_mut() {
+			let OutputVal::Extra(name) = metric else {continue;};
+			if let Some(derived) = self.derived.iter().find(|derived| derived.name.eq_ignore_ascii_case(name)) {
+				*metric = OutputVal::Derived(derived.clone());
+			}//end if there's a derived metric with this name
+		}//end resolving each metric
+		return Ok(());
+	}//end resolve_derived()
+
+	/// Copies these settings, converting areas with calibration.
+	pub fn with_calibration(&self, calibration: Option<Calibration>) -> OutputSettings {
+		let mut settings = self.clone();
+		settings.calibration = calibration;
+		settings.metrics = settings.with_units(settings.metrics.clone());
+		return settings;
+	}//end with_calibration()
+
+	/// If there's a calibration, replaces Area1 and Area2 in vals
+	/// with their values in mm², or adds the mm² values after the
+	/// pixel values, depending on the calibration.
+	/// 
+	/// # Examples
+	/// ```
+	/// use milo_excel_helper::{calibration::{Calibration, CalibratedReport}, excel::{OutputSettings, OutputVal}};
+	/// let calibration = Calibration {mm2_per_px: 0.01, report: CalibratedReport::Alongside, source: "254 DPI".to_string()};
+	/// let settings = OutputSettings::default().with_calibration(Some(calibration));
+	/// let names: Vec<String> = settings.metrics.iter().map(|val| val.name()).collect();
+	/// assert_eq!(names, vec!["Area1 (px)", "Area1 (mm²)", "Area2 (px)", "Area2 (mm²)", "%Area2"]);
+	/// ```
+	pub fn with_units(&self, vals: Vec<OutputVal>) -> Vec<OutputVal> {
+		let Some(calibration) = &self.calibration else {return vals;};
+		let mut converted = Vec::new();
+		for val in vals {
+			match val {
+				OutputVal::KernelArea | OutputVal::EndospermArea => {
+					if calibration.report == CalibratedReport::Alongside {
+						converted.push(OutputVal::InUnits(Box::new(val.clone()), AreaUnit::Pixels));
+					}//end if we're keeping pixel values
+					converted.push(OutputVal::InUnits(Box::new(val), AreaUnit::Mm2(calibration.mm2_per_px)));
+				},
+				val => converted.push(val),
+			}//end matching whether val is an area
+		}//end converting each val
+		return converted;
+	}//end with_units()
+}//end impl for OutputSettings
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum DataVal {
 	String(String),
 	Integer(i32),
-	Float(f32),
+	Float(f64),
+	/// A missing value, written as a blank cell.
+	Empty,
+	/// An Excel formula, with {row} standing for the number of the
+	/// row it's written to and {colN} for the letter of the chunk's
+	/// Nth column, along with the value it should give, which is
+	/// saved as its cached result.
+	Formula(String, Box<DataVal>),
 }
 
 impl DataVal {
 	pub fn str(str: &str) -> DataVal {DataVal::String(str.to_string())}
+	/// Integer if there's a value, otherwise Empty.
+	pub fn opt_int(val: Option<i32>) -> DataVal {val.map_or(DataVal::Empty, DataVal::Integer)}
+	/// Float if there's a value, otherwise Empty.
+	pub fn opt_float(val: Option<f32>) -> DataVal {val.map_or(DataVal::Empty, |val| DataVal::Float(val as f64))}
+	/// Float for a value, Empty if there was no data, or n/a if
+	/// the statistic isn't defined for the data.
+	pub fn stat(stat: Stat) -> DataVal {
+		match stat {
+			Stat::Value(val) => DataVal::Float(val),
+			Stat::Empty => DataVal::Empty,
+			Stat::Undefined => DataVal::str("n/a"),
+		}//end matching whether stat has a value
+	}
+	/// A formula giving cached.
+	pub fn formula(formula: String, cached: DataVal) -> DataVal {DataVal::Formula(formula, Box::new(cached))}
+	/// The text of this value, as saved for a formula's result.
+	fn result_text(&self) -> String {
+		match self {
+			DataVal::String(s) => s.clone(),
+			DataVal::Integer(i) => i.to_string(),
+			DataVal::Float(f) => f.to_string(),
+			DataVal::Empty => String::new(),
+			DataVal::Formula(_, cached) => cached.result_text(),
+		}//end matching self
+	}
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+/// How the chunks of a sheet are placed relative to each other.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChunkLayout {
+	/// Each chunk goes below the one before it.
+	#[default]
+	Vertical,
+	/// Each chunk goes to the right of the one before it, with
+	/// their headers on the same row.
+	SideBySide,
+}//end enum ChunkLayout
+
+/// Settings for which sheets get native Excel charts. None do
+/// unless turned on in the config.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[serde(default)]
+pub struct ChartSettings {
+	/// Whether each stats sheet gets a column chart of Avg for each
+	/// sample, with Std as error bars.
+	pub stats: bool,
+	/// Whether the sum sheet gets a line chart of %Area2, with a line for each file.
+	pub sum: bool,
+	/// Whether each sheet of the summary book gets a chart comparing
+	/// the csv files that went into it.
+	pub summary: bool,
+	/// Names of sheets which shouldn't get a chart, like "kernel-stats".
+	pub skip: Vec<String>,
+}//end struct ChartSettings
+
+impl Default for ChartSettings {
+	fn default() -> ChartSettings {
+		ChartSettings {stats: false, sum: false, summary: false, skip: Vec::new()}
+	}//end default()
+}//end impl Default for ChartSettings
+
+impl ChartSettings {
+	/// Whether the sheet named sheet_name can have a chart, given
+	/// whether that kind of sheet gets charts at all.
+	pub fn allows(&self, kind_enabled: bool, sheet_name: &str) -> bool {
+		kind_enabled && !self.skip.iter().any(|skipped| skipped.eq_ignore_ascii_case(sheet_name))
+	}//end allows()
+}//end impl for ChartSettings
+
+/// How strongly a value outside a threshold is pointed out.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ThresholdLevel {
+	#[default]
+	Red,
+	Amber,
+}//end enum ThresholdLevel
+
+/// Limits for the values in every column with a certain header,
+/// like CV above 0.1. Limits are in the units of the values, so
+/// percentages are fractions, as 10% is 0.1.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+pub struct ThresholdRule {
+	/// The header of the columns to check, like "CV" or "Split Diff".
+	pub column: String,
+	/// Values above this are outside the threshold.
+	pub above: Option<f64>,
+	/// Values below this are outside the threshold.
+	pub below: Option<f64>,
+	/// How values outside the threshold are shown.
+	#[serde(default)]
+	pub level: ThresholdLevel,
+}//end struct ThresholdRule
+
+impl ThresholdRule {
+	/// Checks that there's at least one limit, and that the limits
+	/// leave some values inside the threshold.
+	pub fn check(&self) -> Result<(), String> {
+		match (self.below, self.above) {
+			(None, None) => Err(format!("Threshold for {} needs a value for above, below, or both", self.column)),
+			(Some(below), Some(above)) if below > above => Err(format!("Threshold for {} has below ({}) bigger than above ({})", self.column, below, above)),
+			_ => Ok(()),
+		}//end matching which limits we have
+	}//end check()
+
+	/// An Excel formula for whether the value in cell is a number
+	/// outside this threshold.
+	fn formula(&self, cell: &str) -> String {
+		let mut outside = Vec::new();
+		if let Some(above) = self.above {outside.push(format!("{}>{}", cell, above));}
+		if let Some(below) = self.below {outside.push(format!("{}<{}", cell, below));}
+		format!("=AND(ISNUMBER({}),OR({}))", cell, outside.join(","))
+	}//end formula()
+
+	/// The format for values outside this threshold.
+	fn format(&self) -> Format {
+		match self.level {
+			ThresholdLevel::Red => Format::new().set_background_color(Color::RGB(0xFFC7CE)).set_font_color(Color::RGB(0x9C0006)),
+			ThresholdLevel::Amber => Format::new().set_background_color(Color::RGB(0xFFEB9C)).set_font_color(Color::RGB(0x9C5700)),
+		}//end matching level
+	}//end format()
+}//end impl for ThresholdRule
+
+/// A measurement of each kernel which can be output.  
+/// In the config file, each is written as its column name, like
+/// "Area1", "%Area2" or "Perimeter", or the name of a derived metric.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(from = "String")]
 pub enum OutputVal {
 	KernelArea,
 	EndospermArea,
 	PercentArea,
+	/// An extra column from the csv, by header name.
+	Extra(String),
+	/// A value computed from the other values of each line.
+	Derived(DerivedMetric),
+	/// An area value in a particular unit, shown in its header.
+	InUnits(Box<OutputVal>, AreaUnit),
 }//end enum OutputVal
 
+/// A unit for areas, along with how to convert to it from pixels.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AreaUnit {
+	Pixels,
+	/// Square millimeters, holding the area of one pixel in mm².
+	Mm2(f32),
+}//end enum AreaUnit
+
+impl From<String> for OutputVal {
+	fn from(name: String) -> OutputVal {
+		match name.trim() {
+			name if name.eq_ignore_ascii_case("Area1") => OutputVal::KernelArea,
+			name if name.eq_ignore_ascii_case("Area2") => OutputVal::EndospermArea,
+			name if name.eq_ignore_ascii_case("%Area2") => OutputVal::PercentArea,
+			name => OutputVal::Extra(name.to_string()),
+		}//end matching name to a built-in column
+	}//end from()
+}//end impl From<String> for OutputVal
+
+impl OutputVal {
+	/// Gets the value this OutputVal refers to from a line, or
+	/// None if that value is missing.
+	pub fn of(&self, line: &InputLine) -> Option<f32> {
+		match self {
+			OutputVal::KernelArea => line.area1.map(|area| area as f32),
+			OutputVal::EndospermArea => line.area2.map(|area| area as f32),
+			OutputVal::PercentArea => line.perc_area2,
+			OutputVal::Extra(name) => line.get_extra(name),
+			OutputVal::Derived(metric) => metric.expr.eval(line),
+			OutputVal::InUnits(val, AreaUnit::Pixels) => val.of(line),
+			OutputVal::InUnits(val, AreaUnit::Mm2(mm2_per_px)) => val.of(line).map(|area| area * mm2_per_px),
+		}//end matching self
+	}//end of()
+
+	/// Gets the value this OutputVal refers to from a line, for use
+	/// in statistics. This is None if the line was excluded.
+	pub fn counted_of(&self, line: &InputLine) -> Option<f32> {
+		match line.excluded {
+			Some(_) => None,
+			None => self.of(line),
+		}//end matching whether line is excluded
+	}//end counted_of()
+
+	/// Gets the value this OutputVal refers to from a line, ready
+	/// to be written to a cell. Areas are written as integers.
+	pub fn val_of(&self, line: &InputLine) -> DataVal {
+		match self {
+			OutputVal::KernelArea => DataVal::opt_int(line.area1),
+			OutputVal::EndospermArea => DataVal::opt_int(line.area2),
+			OutputVal::InUnits(val, AreaUnit::Pixels) => val.val_of(line),
+			_ => DataVal::opt_float(self.of(line)),
+		}//end matching self
+	}//end val_of()
+
+	/// The column name of this value, as used in headers.
+	pub fn name(&self) -> String {
+		match self {
+			OutputVal::KernelArea => "Area1".to_string(),
+			OutputVal::EndospermArea => "Area2".to_string(),
+			OutputVal::PercentArea => "%Area2".to_string(),
+			OutputVal::Extra(name) => name.clone(),
+			OutputVal::Derived(metric) => metric.name.clone(),
+			OutputVal::InUnits(val, AreaUnit::Pixels) => format!("{} (px)", val.name()),
+			OutputVal::InUnits(val, AreaUnit::Mm2(_)) => format!("{} (mm²)", val.name()),
+		}//end matching self
+	}//end name()
+
+	/// The number of decimal places to show for raw values.
+	pub fn decimals(&self) -> usize {
+		match self {
+			OutputVal::KernelArea | OutputVal::EndospermArea => 0,
+			OutputVal::PercentArea => 1,
+			OutputVal::Extra(_) => 2,
+			OutputVal::Derived(metric) => metric.decimals,
+			OutputVal::InUnits(val, AreaUnit::Pixels) => val.decimals(),
+			OutputVal::InUnits(_, AreaUnit::Mm2(_)) => 3,
+		}//end matching self
+	}//end decimals()
+
+	/// The number of decimal places to show for values like
+	/// averages, which summarize many lines.
+	pub fn summary_decimals(&self) -> usize {
+		match self {
+			OutputVal::Derived(metric) => metric.decimals,
+			_ => self.decimals().max(1),
+		}//end matching self
+	}//end summary_decimals()
+
+	/// Whether values should be shown as percentages.
+	pub fn is_percent(&self) -> bool {
+		match self {
+			OutputVal::Derived(metric) => metric.percent,
+			_ => false,
+		}//end matching self
+	}//end is_percent()
+
+	/// The name of the worksheet holding stats for this value.  
+	/// Characters Excel doesn't allow in sheet names are replaced,
+	/// and the name is cut to Excel's limit of 31 characters.
+	/// 
+	/// # Examples
+	/// ```
+	/// use milo_excel_helper::excel::OutputVal;
+	/// assert_eq!(OutputVal::KernelArea.stats_sheet_name(), "kernel-stats");
+	/// assert_eq!(OutputVal::from("Mean/Intensity".to_string()).stats_sheet_name(), "Mean_Intensity-stats");
+	/// ```
+	pub fn stats_sheet_name(&self) -> String {
+		fn sheet_base(val: &OutputVal) -> String {
+			match val {
+				OutputVal::KernelArea => "kernel".to_string(),
+				OutputVal::EndospermArea => "ndsprm".to_string(),
+				OutputVal::PercentArea => "%Area2".to_string(),
+				OutputVal::Extra(name) | OutputVal::Derived(DerivedMetric {name, ..}) => name.chars()
+					.map(|c| if "[]:*?/\\'".contains(c) {'_'} else {c})
+					.take(31 - "-stats".len())
+					.collect(),
+				OutputVal::InUnits(val, AreaUnit::Pixels) => sheet_base(val),
+				OutputVal::InUnits(val, AreaUnit::Mm2(_)) => sheet_base(val).chars()
+					.take(31 - "-mm2-stats".len())
+					.collect::<String>() + "-mm2",
+			}//end matching val to the start of the sheet name
+		}//end sheet_base()
+		format!("{}-stats", sheet_base(self))
+	}//end stats_sheet_name()
+
+	/// The built-in values, followed by every extra value found in
+	/// files, in the order they first appear.
+	pub fn all_in<'a>(files: impl IntoIterator<Item = &'a InputFile>) -> Vec<OutputVal> {
+		let mut vals = vec![OutputVal::KernelArea, OutputVal::EndospermArea, OutputVal::PercentArea];
+		for line in files.into_iter().flat_map(|file| file.input_lines.iter()) {
+			for (name, _) in line.extra.iter() {
+				let val = OutputVal::Extra(name.clone());
+				if !vals.contains(&val) {vals.push(val);}
+			}//end adding each extra value we haven't seen
+		}//end looking at each line
+		return vals;
+	}//end all_in()
+}//end impl for OutputVal
+
 /// for each in header:
 /// - name of header
 /// - decimal places to display for header
@@ -29,21 +457,93 @@ pub enum OutputVal {
 /// for each in sample_row:
 /// - A row of data. for each in row of data:
 /// 	- individual cells of data
+/// for each in marks:
+/// - row within rows, column, and how to mark that cell
+/// for each in highlights:
+/// - columns, and the conditional format to put over all their rows
+/// 
+/// The title says what the chunk is for, like a file, ordering or
+/// metric, and is used to name it in the workbook.
 #[derive(Clone, Debug, PartialEq)]
 pub struct DataChunk{ 
+	pub title: Option<String>,
 	pub headers: Vec<(String, usize, bool)>,
-	pub rows: Vec<Vec<DataVal>>
+	pub rows: Vec<Vec<DataVal>>,
+	pub marks: Vec<(usize, usize, CellMark)>,
+	pub highlights: Vec<(Vec<usize>, Highlight)>,
 }
 
 impl DataChunk {
 	pub fn new() -> DataChunk {
 		DataChunk {
+			title: None,
 			headers: Vec::new(),
 			rows: Vec::new(),
+			marks: Vec::new(),
+			highlights: Vec::new(),
 		}
 	}
+
+	/// The columns whose header is name, ignoring case.
+	fn cols_named(&self, name: &str) -> Vec<usize> {
+		self.headers.iter().enumerate()
+			.filter(|(_, (header, _, _))| header.eq_ignore_ascii_case(name))
+			.map(|(col_idx, _)| col_idx)
+			.collect()
+	}//end cols_named()
+
+	/// Highlights values outside each rule in the columns it names.
+	pub fn add_thresholds(&mut self, rules: &Vec<ThresholdRule>) {
+		for rule in rules {
+			let cols = self.cols_named(&rule.column);
+			if cols.len() > 0 {self.highlights.push((cols, Highlight::Threshold(rule.clone())));}
+		}//end adding each rule which has columns here
+	}//end add_thresholds()
+
+	/// Puts a single color scale over every column named name.
+	pub fn add_heatmap(&mut self, name: &str) {
+		let cols = self.cols_named(name);
+		if cols.len() > 0 {self.highlights.push((cols, Highlight::ColorScale));}
+	}//end add_heatmap()
+
+	/// Marks every cell of the last row added.
+	pub fn mark_last_row(&mut self, mark: CellMark) {
+		let Some(row) = self.rows.last() else {return;};
+		let row_idx = self.rows.len() - 1;
+		for col_idx in 0..row.len() {self.marks.push((row_idx, col_idx, mark));}
+	}//end mark_last_row()
 }
 
+/// Conditional formatting over whole columns of a chunk, which Excel
+/// keeps up to date if values are changed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Highlight {
+	/// Colors numbers outside the threshold.
+	Threshold(ThresholdRule),
+	/// Colors numbers from red for the lowest, through yellow, to
+	/// green for the highest, across all the columns together.
+	ColorScale,
+}//end enum Highlight
+
+/// A way of drawing attention to a cell when it's written.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellMark {
+	/// The kernel was left out of statistics, shown struck through in grey.
+	Excluded,
+	/// The value is an outlier among the scans of its sample, shown in red.
+	Outlier,
+}//end enum CellMark
+
+impl CellMark {
+	/// Changes format to show this mark.
+	fn apply(&self, format: Format) -> Format {
+		match self {
+			CellMark::Excluded => format.set_font_strikethrough().set_font_color(Color::Gray),
+			CellMark::Outlier => format.set_background_color(Color::RGB(0xFFC7CE)).set_font_color(Color::RGB(0x9C0006)),
+		}//end matching self
+	}//end apply()
+}//end impl for CellMark
+
 pub fn get_workbook() -> Workbook {
 	Workbook::new()
 }
@@ -53,342 +553,446 @@ pub fn close_workbook(workbook: &mut Workbook, output_path: &PathBuf) -> Result<
 	Ok(())
 }
 
-pub fn extract_labelled_chunks(data: &Vec<InputFile>) -> Vec<DataChunk> {
+/// Lines up the lines of several files by sample label.  
+/// Returns one row for each label used by any of the files, in
+/// canonical order, along with the line each file has for that
+/// label, if it has one.  
+/// Since files with an unknown ordering are labelled by position
+/// (?1, ?2, etc), they only line up with each other.
+pub fn align_rows<'a>(files: &Vec<&'a InputFile>, alignment: Alignment) -> Vec<(String, Vec<Option<&'a InputLine>>)> {
+	let labelled_files: Vec<Vec<(String, &InputLine)>> = files.iter()
+		.map(|file| file.get_labelled_lines(alignment))
+		.collect();
+	let all_labels = sort_labels(labelled_files.iter()
+		.flat_map(|lines| lines.iter().map(|(label, _)| label.clone()))
+		.collect());
+	all_labels.into_iter()
+		.map(|label| {
+			let lines = labelled_files.iter()
+				.map(|lines| lines.iter().find(|(l, _)| *l == label).map(|(_, line)| *line))
+				.collect();
+			(label, lines)
+		})
+		.collect()
+}//end align_rows()
+
+pub fn extract_labelled_chunks(data: &Vec<InputFile>, settings: &OutputSettings) -> Vec<DataChunk> {
 	let mut chunks = Vec::new();
 	for file in data {
 		let mut chunk = DataChunk::new();
+		chunk.title = Some(file.file_id.clone());
 		chunk.headers.push(("Sample".to_string(),0, false));
 		chunk.headers.push(("FileID".to_string(),0, false));
 		chunk.headers.push(("GridIdx".to_string(),0, false));
-		chunk.headers.push(("Area1".to_string(),0, false));
-		chunk.headers.push(("Area2".to_string(),0, false));
-		chunk.headers.push(("%Area2".to_string(),1, false));
-
-		let sample_labels = file.sample_ordering.get_labels();
-		for (i,line) in file.input_lines.iter().enumerate() {
-			let label = sample_labels.get(i).unwrap_or(&"???");
-			chunk.rows.push(vec![
+		let metrics = settings.with_units(OutputVal::all_in([file]));
+		for metric in metrics.iter() {
+			chunk.headers.push((metric.name(), metric.decimals(), false));
+		}//end adding a header for each metric
+
+		let any_excluded = file.input_lines.iter().any(|line| line.excluded.is_some());
+		if any_excluded {chunk.headers.push(("Excluded".to_string(),0, false));}
+
+		let sample_labels = file.get_labels(settings.alignment);
+		for (line, label) in file.input_lines.iter().zip(sample_labels.iter()) {
+			let mut row = vec![
 				DataVal::str(label),
 				DataVal::str(&file.file_id),
 				DataVal::Integer(line.grid_idx),
-				DataVal::Integer(line.area1),
-				DataVal::Integer(line.area2),
-				DataVal::Float(line.perc_area2)
-			]);
+			];
+			row.extend(metrics.iter().map(|metric| metric.val_of(line)));
+			if any_excluded {row.push(DataVal::str(line.excluded.as_deref().unwrap_or("")));}
+			chunk.rows.push(row);
+			if line.excluded.is_some() {chunk.mark_last_row(CellMark::Excluded);}
 		}//end going over each line
 
+		chunk.add_thresholds(&settings.thresholds);
 		chunks.push(chunk);
 	}//end looping over files in data
 	return chunks;
 }//end extract_labelled_chunks()
 
-pub fn extract_sorted_chunks_1(data: &Vec<InputFile>) -> Vec<DataChunk> {
+pub fn extract_sorted_chunks_1(data: &Vec<InputFile>, settings: &OutputSettings) -> Vec<DataChunk> {
 	// each chunk is an ordering, so ab51 or ba15
 	// thus, before creating chunks, must sort out input by ordering
 	// sorting input by ordering can be done in one line with .iter.filter.collect, so
 	// we should create an inner function which just returns a chunk for a list of
 	// input files, and then we can call that super easily on any sorting we need
 
-	fn extract_sorted_chunk_1_helper(files: &Vec<&InputFile>) -> DataChunk {
+	fn extract_sorted_chunk_1_helper(files: &Vec<&InputFile>, settings: &OutputSettings) -> DataChunk {
 		let mut chunk = DataChunk::new();
+		chunk.title = files.first().map(|file| file.sample_ordering.name.clone());
 
 		// add the headers to chunk
 		chunk.headers.push(("Sample".to_string(),0, false));
-		// // add Area1,Area2,%Area2 for each file, then av, std, cv
+		// // add Area1,Area2,%Area2 and any extra values for each file, then av, std, cv
+		let metrics = settings.with_units(OutputVal::all_in(files.iter().copied()));
 		for _ in 0..(files.len()/* + 3*/) {
 			chunk.headers.push(("".to_string(),0, false));
-			chunk.headers.push(("Area1".to_string(),0, false));
-			chunk.headers.push(("Area2".to_string(),0, false));
-			chunk.headers.push(("%Area2".to_string(),1, false));
+			for metric in metrics.iter() {
+				chunk.headers.push((metric.name(), metric.decimals(), false));
+			}//end adding a header for each metric
 		}//end adding Area headers
 
 		// print out data in columns instead of rows
-		let sample_labels = SampleOrder::AB110.get_labels();
-		for sample in sample_labels 
-		{ chunk.rows.push(vec![DataVal::str(sample)]); }
+		let empty = DataVal::str("");
+		for (label, lines) in align_rows(files, settings.alignment) {
+			let mut row = vec![DataVal::str(&label)];
+			for line in lines {
+				row.push(empty.clone());
+				match line {
+					Some(line) => row.extend(metrics.iter().map(|metric| metric.val_of(line))),
+					None => row.extend(metrics.iter().map(|_| DataVal::Empty)),
+				}//end matching whether this file has this sample
+			}//end adding data from each file
+			chunk.rows.push(row);
+		}//end adding a row for each sample
 		
 		// stuff for area, std, cv
 		let mut last_line = vec![DataVal::str("FileID")];
-		let empty = DataVal::str("");
-		
 		for file in files {
-			for (row_idx, row) in InputFile::get_ab15_order(file.sample_ordering, &file.input_lines).iter().enumerate() {
-				let a1 = DataVal::Integer(row.area1);
-				let a2 = DataVal::Integer(row.area2);
-				let a2p = DataVal::Float(row.perc_area2);
-				match chunk.rows.get_mut(row_idx) {
-					Some(row) => row.append(&mut vec![empty.clone(),a1,a2,a2p]),
-					None => chunk.rows.push(vec![DataVal::str("?"),empty.clone(),a1,a2,a2p]),
-				}//end adding data to chunk, regardless of whether we have sample
-			}//end looping over the lines of data in this file
-			last_line.push(empty.clone());
 			last_line.push(empty.clone());
-			last_line.push(DataVal::str(&file.file_id));
-			last_line.push(empty.clone());
-		}//end adding data to chunk for each file
+			for metric_idx in 0..metrics.len() {
+				match metric_idx {
+					1 => last_line.push(DataVal::str(&file.file_id)),
+					_ => last_line.push(empty.clone()),
+				}//end matching whether this is the column for the file id
+			}//end adding a cell under each metric
+		}//end adding file id for each file
 		chunk.rows.push(last_line);
+		chunk.add_thresholds(&settings.thresholds);
+		if settings.heatmap {chunk.add_heatmap(&OutputVal::PercentArea.name());}
 
 		// TODO: Add average, stdev, csv
 
 		return chunk;
 	}//end extract_chunk_1_helper()
 
-	// get vector of unique SampleOrder values
-	let mut all_orderings = data.iter()
-		.map(|f| f.sample_ordering)
-		.collect::<Vec<SampleOrder>>();
-	all_orderings.sort_unstable();
-	all_orderings.dedup();
+	// get vector of unique SampleOrder values, in the order they first appear
+	let mut all_orderings: Vec<&SampleOrder> = Vec::new();
+	for file in data.iter() {
+		if !all_orderings.contains(&&file.sample_ordering) {all_orderings.push(&file.sample_ordering);}
+	}//end finding each unique ordering
 
 	let mut chunks = Vec::new();
 
 	for ordering in all_orderings {
 		let files = data.iter()
-			.filter(|f| f.sample_ordering == ordering)
+			.filter(|f| f.sample_ordering == *ordering)
 			.collect();
-		let chunk = extract_sorted_chunk_1_helper(&files);
+		let chunk = extract_sorted_chunk_1_helper(&files, settings);
 		chunks.push(chunk);
 	}//end getting a chunk of files for each ordering we found
 
 	return chunks;
 }//end extract_sorted_chunks_1()
 
-pub fn extract_sorted_chunks_2(data: &Vec<InputFile>) -> Vec<DataChunk> {
-	// simple little functions to avoid repeating things
-	fn i_to_val(input: &InputLine, idx: i32) -> DataVal {
-		match idx {
-			1 => DataVal::Integer(input.area1),
-			2 => DataVal::Integer(input.area2),
-			0 => DataVal::Float(input.perc_area2),
-			_ => DataVal::str("????")
-		}//end matching index to property we want
-	}//end i_to_val
-	fn i_to_label(idx: i32) -> String {
-		match idx {
-			1 => "Area1".to_string(),
-			2 => "Area2".to_string(),
-			0 => "%Area2".to_string(),
-			_ => "Unknown".to_string()
-		}//end matching index to column label
-	}//end i_to_label
-	
+pub fn extract_sorted_chunks_2(data: &Vec<InputFile>, settings: &OutputSettings) -> Vec<DataChunk> {
 	let mut chunks = Vec::new();
+	let aligned_rows = align_rows(&data.iter().collect(), settings.alignment);
+	let mut metrics = vec![OutputVal::PercentArea, OutputVal::KernelArea, OutputVal::EndospermArea];
+	for metric in OutputVal::all_in(data) {
+		if !metrics.contains(&metric) {metrics.push(metric);}
+	}//end adding any extra metrics after the built-in ones
+	let metrics = settings.with_units(metrics);
 
-	for i in 0..=2 {
+	for metric in metrics {
 		let mut chunk = DataChunk::new();
+		chunk.title = Some(metric.name());
 		// add the headers
 		chunk.headers.push(("Sample".to_string(),0, false));
 		for _ in data.iter()
-		{ chunk.headers.push((i_to_label(i),1, false)); }
+		{ chunk.headers.push((metric.name(),metric.decimals().max(1), false)); }
 
 		// add the data
-		let sample_labels = SampleOrder::AB110.get_labels();
-		sample_labels
-			.iter()
-			.map(|elem| DataVal::str(elem))
-			.for_each(|elem| chunk.rows.push(vec![elem]));
+		for (label, lines) in aligned_rows.iter() {
+			let mut row = vec![DataVal::str(label)];
+			for line in lines {
+				match line {
+					Some(line) => row.push(metric.val_of(line)),
+					None => row.push(DataVal::Empty),
+				}//end matching whether this file has this sample
+			}//end adding data from each file
+			chunk.rows.push(row);
+		}//end adding a row for each sample
 		let mut last_line = vec![DataVal::str("FileID")];
-		for (col_idx, file) in data.iter().enumerate() {
-			for (row_idx, row) in InputFile::get_ab15_order(file.sample_ordering, &file.input_lines).iter().enumerate() {
-				let this_row = match chunk.rows.get_mut(row_idx) {
-					Some(chunk_row) => chunk_row,
-					None => {
-						while !(row_idx < chunk.rows.len()) {
-							let mut new_placeholder_row = Vec::new();
-							for _ in 0..(col_idx+1)
-							{ new_placeholder_row.push(DataVal::str("??")) }
-							chunk.rows.push(new_placeholder_row);
-						}//end populating empty space so we're in the right position
-						chunk.rows.last_mut().unwrap()
-					}//end case that we need to create a row to reference
-				};// end getting reference for this row
-				this_row.push(i_to_val(row, i));
-			}//end looping over rows in the file
+		for file in data.iter() {
 			last_line.push(DataVal::str(&file.file_id));
 		}//end looping over files
 		chunk.rows.push(last_line);
 
+		chunk.add_thresholds(&settings.thresholds);
+		if settings.heatmap {chunk.add_heatmap(&OutputVal::PercentArea.name());}
 		chunks.push(chunk);
-	}//end looping over val indices
+	}//end looping over metrics
 
 	return chunks;
 }//end extract_sorted_chunks_2()
 
-pub fn extract_sum_chunk(data: &Vec<InputFile>, output_val: OutputVal) -> DataChunk {
+/// Adds headers for the robust statistics of output_val to chunk,
+/// in the order add_robust_vals() adds them.
+fn add_robust_headers(chunk: &mut DataChunk, output_val: &OutputVal) {
+	chunk.headers.push(("Median".to_string(),output_val.summary_decimals(),output_val.is_percent()));
+	chunk.headers.push(("IQR".to_string(),output_val.summary_decimals(),output_val.is_percent()));
+	chunk.headers.push(("MAD".to_string(),output_val.summary_decimals(),output_val.is_percent()));
+	chunk.headers.push(("Trim Mean".to_string(),output_val.summary_decimals(),output_val.is_percent()));
+}//end add_robust_headers()
+
+/// Adds the median, IQR, MAD and trimmed mean of values to row.
+fn add_robust_vals(row: &mut Vec<DataVal>, values: &Vec<Option<f32>>, settings: &OutputSettings) {
+	let robust = robust_summarize(values.iter().flatten().copied(), settings.trim_fraction);
+	row.push(DataVal::stat(robust.median));
+	row.push(DataVal::stat(robust.iqr));
+	row.push(DataVal::stat(robust.mad));
+	row.push(DataVal::stat(robust.trimmed_mean));
+}//end add_robust_vals()
+
+pub fn extract_sum_chunk(data: &Vec<InputFile>, output_val: &OutputVal, settings: &OutputSettings) -> DataChunk {
 	let mut chunk = DataChunk::new();
+	chunk.title = Some(output_val.name());
 	// add the headers
 	chunk.headers.push(("Sample".to_string(),0, false));
 	for _ in data.iter() {
-		match output_val {
-			OutputVal::KernelArea => chunk.headers.push(("Area1".to_string(),1, false)),
-			OutputVal::EndospermArea => chunk.headers.push(("Area2".to_string(),1, false)),
-			OutputVal::PercentArea => chunk.headers.push(("%Area2".to_string(),1, false)),
-		}//end outputting header based on output_val
+		chunk.headers.push((output_val.name(),output_val.summary_decimals(), output_val.is_percent()));
 	}//end adding headers based on output_val for each file
 	chunk.headers.push(("".to_string(),0, false));
-	chunk.headers.push(("Avg".to_string(),1, false));
+	chunk.headers.push(("n".to_string(),0, false));
+	chunk.headers.push(("Avg".to_string(),output_val.summary_decimals(), output_val.is_percent()));
 	chunk.headers.push(("Std".to_string(),2, false));
 	chunk.headers.push(("CV".to_string(),2, true));
+	if settings.robust {add_robust_headers(&mut chunk, output_val);}
 	
-	// add sample labels
-	let sample_labels = SampleOrder::AB110.get_labels();
-	sample_labels.iter()
-		.map(|lbl| DataVal::str(lbl))
-		.for_each(|lbl| chunk.rows.push(vec![lbl]));
-
-	// add %Area2 for each file
-	for (col_idx, file) in data.iter().enumerate() {
-		for (line_idx, line) in 
-		InputFile::get_ab15_order(
-			file.sample_ordering,
-			&file.input_lines
-		).iter().enumerate() {
-			// gets reference to current row. Error validation in case of unlabelled samples
-			let this_chunk_row_ref = match chunk.rows.get_mut(line_idx) {
-				Some(chunk_row) => chunk_row,
-				None => {
-					while !(line_idx < chunk.rows.len()) {
-						let mut new_placeholder_row = Vec::new();
-						for _ in 0..(col_idx+1)
-						{ new_placeholder_row.push(DataVal::str("??")); }
-						chunk.rows.push(new_placeholder_row);
-					}//end populating empty space so we're in the right position
-					chunk.rows.last_mut().unwrap()
-				}//end case that we need to create a row to reference
-			};//end getting reference for this row
-			match output_val {
-				OutputVal::KernelArea => this_chunk_row_ref.push(DataVal::Float(line.area1 as f32)),
-				OutputVal::EndospermArea => this_chunk_row_ref.push(DataVal::Float(line.area2 as f32)),
-				OutputVal::PercentArea => this_chunk_row_ref.push(DataVal::Float(line.perc_area2)),
-			}//end matching the kind of sum to do
-		}//end looping over lines in the file
-	}//end looping over files to include
-
-	// Add avg, std, cv
-	for row in chunk.rows.iter_mut() {
-		let data_slice: &Vec<f32> = &row[1..].iter()
-			.filter_map(|d| match d {
-				DataVal::Float(f) => Some(f),
-				_ => None,})
-			.map(|f| f.clone())
-			.collect::<Vec<f32>>();
+	// add the value from each file for each sample, then avg, std, cv
+	for (label, lines) in align_rows(&data.iter().collect(), settings.alignment) {
+		let data_slice: &Vec<Option<f32>> = &lines.iter()
+			.map(|line| line.and_then(|line| output_val.counted_of(line)))
+			.collect();
+		let mut row = vec![DataVal::str(&label)];
+		row.extend(data_slice.iter().map(|val| DataVal::opt_float(*val)));
 		row.push(DataVal::str(""));
-		row.push(DataVal::Float(crate::math::avg(data_slice)));
-		row.push(DataVal::Float(crate::math::std(data_slice)));
-		row.push(DataVal::Float(crate::math::cv(data_slice)));
-	}//end adding avg, std, cv for each row
+		// n, avg, std and cv are formulas over the values, so they update if values are changed
+		let summary = summarize(data_slice.iter().flatten().copied(), settings.sd_estimator);
+		let values = format!("{{col1}}{{row}}:{{col{}}}{{row}}", data.len());
+		let stdev = match settings.sd_estimator {
+			SdEstimator::Sample => "STDEV",
+			SdEstimator::Population => "STDEVP",
+		};//end matching which Excel function gives our kind of SD
+		let (avg_cell, std_cell) = (format!("{{col{}}}", row.len() + 1), format!("{{col{}}}", row.len() + 2));
+		row.push(DataVal::formula(format!("=COUNT({})", values), DataVal::Integer(summary.n as i32)));
+		row.push(DataVal::formula(format!("=IF(COUNT({0})=0,\"\",AVERAGE({0}))", values), DataVal::stat(summary.mean)));
+		row.push(DataVal::formula(format!("=IF(COUNT({0})=0,\"\",IFERROR({1}({0}),\"n/a\"))", values, stdev), DataVal::stat(summary.sd)));
+		row.push(DataVal::formula(format!("=IF(COUNT({0})=0,\"\",IFERROR({1}{{row}}/{2}{{row}},\"n/a\"))", values, std_cell, avg_cell), DataVal::stat(summary.cv)));
+		if settings.robust {add_robust_vals(&mut row, data_slice, settings);}
+		chunk.rows.push(row);
+		for (file_idx, _) in settings.outliers.find(data_slice) {
+			chunk.marks.push((chunk.rows.len() - 1, file_idx + 1, CellMark::Outlier));
+		}//end marking each outlier among the files
+	}//end adding a row for each sample
 
+	chunk.add_thresholds(&settings.thresholds);
 	return chunk;
 }//end extract_sum_chunk()
 
-pub fn extract_stats_chunk(data: &Vec<InputFile>, output_val: OutputVal) -> DataChunk {
+/// The split avg, std, cv and diff of the section averages of a
+/// kernel. If any section has no average, as when all its values
+/// were excluded or missing, every split value is n/a, rather than
+/// being worked out from the other sections.
+///
+/// # Examples
+/// ```
+/// use milo_excel_helper::{excel::{split_vals, DataVal}, math::Stat};
+/// let vals = split_vals(&vec![Stat::Value(1000.), Stat::Value(1040.)]);
+/// assert_eq!(vals[0], DataVal::Float(1020.));
+/// assert_eq!(vals[3], DataVal::Float(40.));
+/// let vals = split_vals(&vec![Stat::Value(1000.), Stat::Empty]);
+/// assert!(vals.iter().all(|val| *val == DataVal::str("n/a")));
+/// ```
+pub fn split_vals(section_avgs: &Vec<Stat>) -> Vec<DataVal> {
+	let Some(avgs) = section_avgs.iter().map(Stat::value).collect::<Option<Vec<f64>>>() else {
+		return vec![DataVal::stat(Stat::Undefined); 4];
+	};//end if any section is missing its average
+	// the sections are the whole kernel rather than a sample of it, so this stays a population SD
+	let split = summarize(avgs, SdEstimator::Population);
+	let s_diff = match (split.max, split.min) {
+		(Stat::Value(max), Stat::Value(min)) => Stat::Value(max - min),
+		(max, _) => max,
+	};//end matching whether we have a range
+	// split avg, split std, split cv, split diff
+	return vec![DataVal::stat(split.mean), DataVal::stat(split.sd), DataVal::stat(split.cv), DataVal::stat(s_diff)];
+}//end split_vals()
+
+pub fn extract_stats_chunk(data: &Vec<InputFile>, output_val: &OutputVal, settings: &OutputSettings) -> DataChunk {
 	let mut chunk = DataChunk::new();
+	chunk.title = Some(output_val.name());
 	// add the headers
 	chunk.headers.push(("Sample".to_string(),1,false));
-	chunk.headers.push(("Avg".to_string(),1,false));
-	chunk.headers.push(("Std".to_string(),1,false));
+	chunk.headers.push(("n".to_string(),0,false));
+	chunk.headers.push(("Avg".to_string(),output_val.summary_decimals(),output_val.is_percent()));
+	chunk.headers.push(("Std".to_string(),output_val.summary_decimals(),output_val.is_percent()));
 	chunk.headers.push(("CV".to_string(),1,true));
+	if settings.robust {add_robust_headers(&mut chunk, output_val);}
 	chunk.headers.push(("".to_string(),1,false));
-	chunk.headers.push(("Split Avg".to_string(),1,false));
-	chunk.headers.push(("Split Std".to_string(),1,false));
+	chunk.headers.push(("Split Avg".to_string(),output_val.summary_decimals(),output_val.is_percent()));
+	chunk.headers.push(("Split Std".to_string(),output_val.summary_decimals(),output_val.is_percent()));
 	chunk.headers.push(("Split CV".to_string(),1,true));
-	chunk.headers.push(("Split Diff".to_string(),1,false));
+	chunk.headers.push(("Split Diff".to_string(),output_val.summary_decimals(),output_val.is_percent()));
 
 	// add sample labels, also having overall sample, like ag05-1a
-	let sample_labels = SampleOrder::AB110.get_labels();
 	let filenames: Vec<&str> = data.iter()
 		.map(|file| file.file_id.as_str())
 		.collect();
 	let mut common_sample_id = guess_sample_id(&filenames).unwrap_or("".to_string());
 	if !common_sample_id.eq("") {common_sample_id += "-";}
-	sample_labels.iter()
-		.map(|lbl| DataVal::String(common_sample_id.clone() + lbl))
-		.for_each(|lbl| chunk.rows.push(vec![lbl]));
 
-	// collect %Area2 for each file
+	// collect values for each file
 	// rows_per_sample has each column from file, each row from sample
 	// each inner vec is one row, iterate through one row for cols
-	let mut rows_per_sample: Vec<Vec<f32>> = Vec::new();
-	for (col_idx, file) in data.iter().enumerate() {
-		for (line_idx, line) in
-		InputFile::get_ab15_order(
-			file.sample_ordering,
-			&file.input_lines
-		).iter().enumerate() {
-			let this_row_ref = match rows_per_sample.get_mut(line_idx) {
-				Some(row_ref) => row_ref,
-				None => {
-					while !(line_idx < rows_per_sample.len()) {
-						let mut new_placeholder_row = Vec::new();
-						for _ in 0..(col_idx)
-						{ new_placeholder_row.push(-1.); }
-						rows_per_sample.push(new_placeholder_row);
-					}//end populating empty space so we're in the right position
-					rows_per_sample.last_mut().expect("We just added to the vec, it shouldn't be empty!")
-				}//end case that we need to create a row to reference
-			};//end getting reference for this row
-			match output_val {
-				OutputVal::KernelArea => this_row_ref.push(line.area1 as f32),
-				OutputVal::EndospermArea => this_row_ref.push(line.area2 as f32),
-				OutputVal::PercentArea => this_row_ref.push(line.perc_area2),
-			}
-		}//end looping over samples in ab15 order
-	}//end looping over files
-
-	// Add avg, std, cv per file
-	for i in 0..(rows_per_sample.len()) {
-		// get references for right space in vecs
-		let this_data_row_ref = rows_per_sample.get(i);
-		if this_data_row_ref.is_none() {continue;}
-		let this_data_row_ref = this_data_row_ref.unwrap();
-		let this_chunk_row_ref = match chunk.rows.get_mut(i) {
-			Some(chunk_row) => chunk_row,
-			None => {
-				while !(i < chunk.rows.len()) {
-					let mut new_placeholder_row = Vec::new();
-					for _ in 0..(i+1)
-					{ new_placeholder_row.push(DataVal::str("??")); }
-					chunk.rows.push(new_placeholder_row);
-				}//end populating empty space so we're in the right position
-				chunk.rows.last_mut().expect("We just added to the vec; it shouldn't be empty!")
-			}//end case that we need to create a row to reference
-		};//end getting reference for this row in chunk
+	let aligned_rows = align_rows(&data.iter().collect(), settings.alignment);
+	let rows_per_sample: Vec<(String, Vec<Option<f32>>)> = aligned_rows.iter()
+		.map(|(label, lines)| {
+			let values = lines.iter().map(|line| line.and_then(|line| output_val.counted_of(line))).collect();
+			(label.clone(), values)
+		})
+		.collect();
+
+	// Add avg, std, cv per sample
+	for (i, (label, this_data_row_ref)) in rows_per_sample.iter().enumerate() {
+		let mut this_chunk_row = vec![DataVal::String(common_sample_id.clone() + label)];
 
 		// add per-sample (1a, 1b, 2a, 2b, etc) data
-		let avg = crate::math::avg(this_data_row_ref);
-		let std = crate::math::std(this_data_row_ref);
-		let cv = crate::math::cv(this_data_row_ref);
-		this_chunk_row_ref.push(DataVal::Float(avg));
-		this_chunk_row_ref.push(DataVal::Float(std));
-		this_chunk_row_ref.push(DataVal::Float(cv));
-		this_chunk_row_ref.push(DataVal::str(""));
-
-		// add sample average data (per 1, 2, etc)
-		if i % 2 == 0 {
-			let sa = rows_per_sample.get(i);
-			let sb = rows_per_sample.get(i+1);
-			if sa.is_some() && sb.is_none() {this_chunk_row_ref.append(&mut vec![DataVal::str(""),DataVal::str(""),DataVal::str(""),DataVal::str("")])}
-			else if sa.is_some() && sb.is_some() {
-				let sa = sa.expect("We already checked sa is_some()!?");
-				let sb = sb.expect("We already checked sb is_some()!?");
-				let sa_avg = crate::math::avg(sa);
-				let sb_avg = crate::math::avg(sb);
-				let s_diff = sa_avg.sub(sb_avg).abs();
-				let s_std = crate::math::std(&vec![sa_avg,sb_avg]);
-				let s_avg = (sa_avg + sb_avg) / 2.;
-				let s_cv = s_std / s_avg;
-				// split diff, split std, split avg, split cv
-				this_chunk_row_ref.push(DataVal::Float(s_avg));
-				this_chunk_row_ref.push(DataVal::Float(s_std));
-				this_chunk_row_ref.push(DataVal::Float(s_cv));
-				this_chunk_row_ref.push(DataVal::Float(s_diff));
-			}//end if we found both a and b
-		}//end if we're on an even index
-	}//end looping matching file data to chunk rows for each file
+		let summary = summarize(this_data_row_ref.iter().flatten().copied(), settings.sd_estimator);
+		this_chunk_row.push(DataVal::Integer(summary.n as i32));
+		this_chunk_row.push(DataVal::stat(summary.mean));
+		this_chunk_row.push(DataVal::stat(summary.sd));
+		this_chunk_row.push(DataVal::stat(summary.cv));
+		if settings.robust {add_robust_vals(&mut this_chunk_row, this_data_row_ref, settings);}
+		this_chunk_row.push(DataVal::str(""));
+
+		// add sample average data (per kernel 1, 2, etc) on the first section of each kernel
+		let kernel = parse_label(label).map(|(kernel, _)| kernel);
+		let is_first_section = i == 0 || kernel != parse_label(&rows_per_sample[i - 1].0).map(|(kernel, _)| kernel);
+		if kernel.is_some() && is_first_section {
+			let section_avgs: Vec<Stat> = rows_per_sample[i..].iter()
+				.take_while(|(other_label, _)| parse_label(other_label).map(|(k, _)| k) == kernel)
+				.map(|(_, values)| summarize(values.iter().flatten().copied(), settings.sd_estimator).mean)
+				.collect();
+			if section_avgs.len() > 1 {this_chunk_row.extend(split_vals(&section_avgs));}
+		}//end if we're on the first section of a kernel
+		chunk.rows.push(this_chunk_row);
+	}//end looping over samples
 
+	chunk.add_thresholds(&settings.thresholds);
 	return chunk;
 }//end extract_stats_chunk()
 
+/// Creates a chunk listing every value of metrics which is an
+/// outlier among the scans of its sample, one per row, with the
+/// file it came from so that scan can be repeated.  
+/// If there aren't any outliers, the chunk has a single row saying so.
+pub fn extract_outlier_chunk(data: &Vec<InputFile>, metrics: &Vec<OutputVal>, settings: &OutputSettings) -> DataChunk {
+	let mut chunk = DataChunk::new();
+	chunk.headers.push(("Metric".to_string(),0,false));
+	chunk.headers.push(("Sample".to_string(),0,false));
+	chunk.headers.push(("FileID".to_string(),0,false));
+	chunk.headers.push(("GridIdx".to_string(),0,false));
+	chunk.headers.push(("Value".to_string(),2,false));
+	chunk.headers.push(("Median".to_string(),2,false));
+	chunk.headers.push(("Score".to_string(),2,false));
+
+	let aligned_rows = align_rows(&data.iter().collect(), settings.alignment);
+	for metric in metrics {
+		for (label, lines) in aligned_rows.iter() {
+			let values: Vec<Option<f32>> = lines.iter()
+				.map(|line| line.and_then(|line| metric.counted_of(line)))
+				.collect();
+			let outliers = settings.outliers.find(&values);
+			if outliers.len() == 0 {continue;}
+			let median = math::median(values.iter().flatten().copied());
+			for (file_idx, score) in outliers {
+				chunk.rows.push(vec![
+					DataVal::String(metric.name()),
+					DataVal::str(label),
+					DataVal::str(&data[file_idx].file_id),
+					DataVal::opt_int(lines[file_idx].map(|line| line.grid_idx)),
+					DataVal::opt_float(values[file_idx]),
+					DataVal::stat(median),
+					DataVal::Float(score),
+				]);
+			}//end adding a row for each outlier
+		}//end looking for outliers in each sample
+	}//end looking for outliers in each metric
+	if chunk.rows.len() == 0 {
+		chunk.rows.push(vec![DataVal::str(""),DataVal::str(""),DataVal::str("No outliers found.")]);
+	}//end if there's nothing to report
+
+	return chunk;
+}//end extract_outlier_chunk()
+
+/// Creates a chunk listing every validation finding, one per row.  
+/// If there aren't any findings, the chunk has a single row saying so.
+pub fn extract_validation_chunk(findings: &Vec<Finding>) -> DataChunk {
+	let mut chunk = DataChunk::new();
+	chunk.headers.push(("FileID".to_string(),0,false));
+	chunk.headers.push(("GridIdx".to_string(),0,false));
+	chunk.headers.push(("Issue".to_string(),0,false));
+
+	for finding in findings {
+		let grid_idx = match finding.issue.grid_idx() {
+			Some(grid_idx) => DataVal::Integer(grid_idx),
+			None => DataVal::str(""),
+		};//end matching whether issue is for a single line
+		chunk.rows.push(vec![
+			DataVal::str(&finding.file_id),
+			grid_idx,
+			DataVal::String(finding.issue.to_string()),
+		]);
+	}//end adding a row for each finding
+	if findings.len() == 0 {
+		chunk.rows.push(vec![DataVal::str(""),DataVal::str(""),DataVal::str("No issues found.")]);
+	}//end if there's nothing to report
+
+	return chunk;
+}//end extract_validation_chunk()
+
+/// Creates a chunk listing the metadata of a scan, starting with
+/// the name of the csv file it was read from.  
+/// Values which are whole numbers are written as numbers.
+pub fn extract_run_info_chunk(source: &str, metadata: &RunMetadata) -> DataChunk {
+	let mut chunk = DataChunk::new();
+	chunk.headers.push(("Setting".to_string(),0,false));
+	chunk.headers.push(("Value".to_string(),0,false));
+
+	chunk.rows.push(vec![DataVal::str("Source File"), DataVal::str(source)]);
+	for (name, value) in metadata.entries.iter() {
+		let value = match value.parse::<i32>() {
+			Ok(num) => DataVal::Integer(num),
+			Err(_) => DataVal::str(value),
+		};//end matching whether value is a number
+		chunk.rows.push(vec![DataVal::str(name), value]);
+	}//end adding a row for each setting
+
+	return chunk;
+}//end extract_run_info_chunk()
+
+/// Records the metadata of each scan in the document properties of
+/// workbook, as custom properties, with the source files in the comments.  
+/// If there's more than one scan, each property name starts with
+/// the source file it came from.
+pub fn set_run_properties(workbook: &mut Workbook, runs: &[(String, RunMetadata)]) {
+	let sources: Vec<&str> = runs.iter().map(|(source, _)| source.as_str()).collect();
+	let mut properties = DocProperties::new()
+		.set_comment(format!("Made by milo-excel-helper from {}", sources.join(", ")));
+	for (source, metadata) in runs {
+		for (name, value) in metadata.entries.iter() {
+			let name = match runs.len() {
+				1 => name.clone(),
+				_ => format!("{}: {}", source, name),
+			};//end matching whether we need to say which scan this is from
+			properties = properties.set_custom_property(name, value);
+		}//end adding each setting
+	}//end adding properties for each scan
+	workbook.set_properties(&properties);
+}//end set_run_properties()
+
 /// Assuming a set of filenames has the same sample id,
 /// and assuming that that id is separated by dashes,
 /// attempts to find a common sample id from a list of
@@ -469,11 +1073,207 @@ pub fn guess_sample_id(filenames: &Vec<&str>) -> Option<String> {
 	}//end matching based on number of matches
 }//end guess_sample_id()
 
+/// The kind of a native Excel chart.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChartKind {
+	Column,
+	Line,
+}//end enum ChartKind
+
+/// A series of a SheetChart, taken from a column of one of the
+/// chunks written to the sheet.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SheetSeries {
+	pub name: String,
+	/// The index of the chunk among those written to the sheet.
+	pub chunk: usize,
+	/// The column holding the values.
+	pub col: usize,
+	/// The column holding the size of error bars above and below
+	/// each value, if there should be error bars.
+	pub error_col: Option<usize>,
+}//end struct SheetSeries
+
+/// A native Excel chart to add to a sheet, drawing on the rows of
+/// the chunks written to it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SheetChart {
+	pub kind: ChartKind,
+	pub title: String,
+	/// The name of the value axis.
+	pub y_axis: String,
+	/// The chunk and column holding the category labels.
+	pub categories: (usize, usize),
+	pub series: Vec<SheetSeries>,
+}//end struct SheetChart
+
+/// A column chart of the Avg of each sample in a stats chunk, with
+/// Std as error bars, or None if the chunk doesn't have those columns.
+pub fn stats_chart(chunk: &DataChunk, output_val: &OutputVal) -> Option<SheetChart> {
+	let col_of = |name: &str| chunk.headers.iter().position(|(header, _, _)| header == name);
+	return Some(SheetChart {
+		kind: ChartKind::Column,
+		title: format!("{} by Sample", output_val.name()),
+		y_axis: output_val.name(),
+		categories: (0, 0),
+		series: vec![SheetSeries {name: "Avg".to_string(), chunk: 0, col: col_of("Avg")?, error_col: col_of("Std")}],
+	});
+}//end stats_chart()
+
+/// A line chart of the values of each file in the sum chunk at
+/// chunk_idx, with a line for each file.
+pub fn sum_chart(chunk_idx: usize, output_val: &OutputVal, data: &Vec<InputFile>) -> SheetChart {
+	SheetChart {
+		kind: ChartKind::Line,
+		title: format!("{} by File", output_val.name()),
+		y_axis: output_val.name(),
+		categories: (chunk_idx, 0),
+		series: data.iter().enumerate()
+			.map(|(file_idx, file)| SheetSeries {name: file.file_id.clone(), chunk: chunk_idx, col: file_idx + 1, error_col: None})
+			.collect(),
+	}
+}//end sum_chart()
+
+/// A column chart comparing the Avg of each sample across stats
+/// chunks from different csv files, named by names, or None if
+/// there's less than two to compare. Samples are labelled as in
+/// the first chunk.
+pub fn comparison_chart(chunks: &Vec<DataChunk>, names: &Vec<String>, output_val: &OutputVal) -> Option<SheetChart> {
+	if chunks.len() < 2 {return None;}
+	let series: Option<Vec<SheetSeries>> = chunks.iter().zip(names.iter()).enumerate()
+		.map(|(chunk_idx, (chunk, name))| {
+			let col_of = |header_name: &str| chunk.headers.iter().position(|(header, _, _)| header == header_name);
+			Some(SheetSeries {name: name.clone(), chunk: chunk_idx, col: col_of("Avg")?, error_col: col_of("Std")})
+		})
+		.collect();
+	return Some(SheetChart {
+		kind: ChartKind::Column,
+		title: format!("{} by File", output_val.name()),
+		y_axis: output_val.name(),
+		categories: (0, 0),
+		series: series?,
+	});
+}//end comparison_chart()
+
+/// Extra things to do when writing chunks to a sheet.
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct SheetOptions {
+	/// Charts to add to the right of the data.
+	pub charts: Vec<SheetChart>,
+	/// Whether each chunk is written as an Excel Table.
+	pub tables: bool,
+	/// Whether each chunk gets a workbook-level name for its range.
+	pub named_ranges: bool,
+	/// How chunks are placed on the sheet.
+	pub layout: ChunkLayout,
+	/// Whether chunks with a title get a merged caption row above them.
+	pub captions: bool,
+	/// Whether the first header row and the label column are frozen.
+	pub freeze_panes: bool,
+	/// Whether column widths are fit to their contents.
+	pub autofit: bool,
+}//end struct SheetOptions
+
+impl SheetOptions {
+	/// Options for a sheet with the tables and layout settings ask for.
+	pub fn from_settings(settings: &OutputSettings) -> SheetOptions {
+		SheetOptions {
+			tables: settings.tables,
+			layout: settings.layout,
+			captions: settings.captions,
+			freeze_panes: settings.freeze_panes,
+			autofit: settings.autofit,
+			..Default::default()
+		}
+	}//end from_settings()
+}//end impl for SheetOptions
+
+/// Turns text into a name Excel accepts for tables and named
+/// ranges, like %Area2-stats into PercArea2_stats.
+///
+/// # Examples
+/// ```
+/// use milo_excel_helper::excel::excel_name;
+/// assert_eq!(excel_name("%Area2-stats"), "PercArea2_stats");
+/// assert_eq!(excel_name("2024 run.csv"), "_2024_run_csv");
+/// ```
+pub fn excel_name(text: &str) -> String {
+	let name: String = text.replace('%', "Perc").chars()
+		.map(|c| if c.is_ascii_alphanumeric() || c == '_' {c} else {'_'})
+		.collect();
+	match name.chars().next() {
+		Some(c) if c.is_ascii_alphabetic() || c == '_' => name,
+		_ => format!("_{}", name),
+	}//end matching whether name starts with something Excel allows
+}//end excel_name()
+
+/// Makes headers fit for an Excel Table, which needs each column
+/// to have a different, non-empty header.
+fn table_headers(headers: Vec<String>) -> Vec<String> {
+	let mut unique: Vec<String> = Vec::new();
+	for (col_idx, header) in headers.into_iter().enumerate() {
+		let base = match header.trim() {
+			"" => format!("Column{}", col_idx + 1),
+			_ => header,
+		};//end matching whether header is blank
+		let mut name = base.clone();
+		let mut copy = 2;
+		while unique.iter().any(|other| other.eq_ignore_ascii_case(&name)) {
+			name = format!("{} ({})", base, copy);
+			copy += 1;
+		}//end while name is taken
+		unique.push(name);
+	}//end making each header unique
+	return unique;
+}//end table_headers()
+
 /// Writes a number of chunks of data to a sheet in a workbook
 pub fn write_chunks_to_sheet(
 	workbook: &mut Workbook,
 	chunks: Iter<DataChunk>,
 	sheet_name: &str
+) -> Result<(),XlsxError> {
+	return write_chunks_with_options(workbook, chunks, sheet_name, &SheetOptions::default());
+}//end write_chunks_to_sheet()
+
+/// Fills in the {row} and {colN} placeholders of a formula, for a
+/// chunk whose first column is first_col.
+///
+/// # Examples
+/// ```
+/// use milo_excel_helper::excel::fill_formula;
+/// assert_eq!(fill_formula("=AVERAGE({col1}{row}:{col3}{row})", 5, 0), "=AVERAGE(B5:D5)");
+/// assert_eq!(fill_formula("=AVERAGE({col1}{row}:{col3}{row})", 5, 10), "=AVERAGE(L5:N5)");
+/// ```
+pub fn fill_formula(formula: &str, row: u32, first_col: u16) -> String {
+	let mut filled = String::new();
+	let mut rest = formula.replace("{row}", &row.to_string());
+	while let Some(start) = rest.find("{col") {
+		let Some(len) = rest[start..].find('}') else {break;};
+		let Ok(col) = rest[start + 4..start + len].parse::<u16>() else {
+			filled.push_str(&rest[..start + len + 1]);
+			rest = rest[start + len + 1..].to_string();
+			continue;
+		};//end if this isn't a column placeholder
+		filled.push_str(&rest[..start]);
+		filled.push_str(&column_number_to_name(first_col + col));
+		rest = rest[start + len + 1..].to_string();
+	}//end replacing each column placeholder
+	filled.push_str(&rest);
+	return filled;
+}//end fill_formula()
+
+/// Writes a number of chunks of data to a sheet in a workbook, as
+/// tables and with names if options say so, then adds any charts
+/// drawn from them to the right of the data.  
+/// Chunks are named after the sheet and their title, or their
+/// position on the sheet if they don't have one. With captions on,
+/// the title is also written in a merged row above the chunk.
+pub fn write_chunks_with_options(
+	workbook: &mut Workbook,
+	chunks: Iter<DataChunk>,
+	sheet_name: &str,
+	options: &SheetOptions,
 ) -> Result<(),XlsxError> {
 	// set up a new sheet in the workbook
 	let sheet = workbook.add_worksheet();
@@ -482,13 +1282,50 @@ pub fn write_chunks_to_sheet(
 	// create a few formats to use later
 	let bold = Format::new().set_bold().set_align(FormatAlign::Center);
 	let default_format = Format::new().set_align(FormatAlign::Center);
+	let caption_format = Format::new().set_bold().set_font_size(12).set_align(FormatAlign::Center).set_border_bottom(FormatBorder::Thin);
+
+	// side by side, every chunk gets a caption row if any does, so the headers line up
+	let chunks: Vec<&DataChunk> = chunks.collect();
+	let any_titled = chunks.iter().any(|chunk| chunk.title.is_some());
+	let has_caption = |chunk: &DataChunk| options.captions && match options.layout {
+		ChunkLayout::Vertical => chunk.title.is_some(),
+		ChunkLayout::SideBySide => any_titled,
+	};//end closure has_caption
 
 	// actually start writing all the data to everything
-	let mut chunk_row = 0;
-	for chunk in chunks {
+	let (mut chunk_row, mut chunk_col) = (0, 0);
+	// the first row of data, number of rows and first column of each chunk, for charts
+	let mut chunk_spans: Vec<(u32, u32, u16)> = Vec::new();
+	// the first column to the right of every chunk
+	let mut right_edge = 0;
+	// the row and first and last columns of each caption, with its text
+	let mut captions: Vec<(u32, u16, u16, String)> = Vec::new();
+	let mut first_header_row = None;
+	// the name, and first and last rows and columns, of each chunk to name
+	let mut named_ranges: Vec<(String, u32, u32, u16, u16)> = Vec::new();
+	let mut used_names: Vec<String> = Vec::new();
+	for (chunk_idx, chunk) in chunks.iter().enumerate() {
+		let width = chunk.headers.len().max(chunk.rows.iter().map(|row| row.len()).max().unwrap_or(0));
+		let last_col = chunk_col + width.max(1) as u16 - 1;
+		right_edge = right_edge.max(chunk_col + width as u16);
+		if has_caption(chunk) {
+			captions.push((chunk_row, chunk_col, last_col, chunk.title.clone().unwrap_or_default()));
+			chunk_row += 1;
+		}//end if this chunk gets a caption
+		let header_row = chunk_row;
+		first_header_row.get_or_insert(header_row);
+		chunk_spans.push((header_row + 1, chunk.rows.len() as u32, chunk_col));
+		let base_name = excel_name(&format!("{}_{}", sheet_name, chunk.title.clone().unwrap_or((chunk_idx + 1).to_string())));
+		let mut chunk_name = base_name.clone();
+		let mut copy = 2;
+		while used_names.iter().any(|other| other.eq_ignore_ascii_case(&chunk_name)) {
+			chunk_name = format!("{}_{}", base_name, copy);
+			copy += 1;
+		}//end while name is taken
+		used_names.push(chunk_name.clone());
 		// write the header row
 		for (index, header) in chunk.headers.iter().enumerate() {
-			let index = index as u16;
+			let index = chunk_col + index as u16;
 			sheet.write_with_format(
 				chunk_row,
 				index,
@@ -500,7 +1337,8 @@ pub fn write_chunks_to_sheet(
 		// create formats for each header row, based on chunk info
 		let mut formats = Vec::new();
 		for (_,decimals,is_percent) in chunk.headers.iter() {
-			let mut num_format = String::from("0.");
+			let mut num_format = String::from("0");
+			if *decimals > 0 {num_format.push_str(".");}
 			for _ in 0..*decimals {num_format.push_str("0");}
 			if *is_percent {num_format.push_str("%");}
 			let this_format = Format::new()
@@ -511,22 +1349,127 @@ pub fn write_chunks_to_sheet(
 
 		// actually get around to writing the data for this chunk
 		chunk_row += 1;
-		for row in chunk.rows.iter() {
+		for (row_idx, row) in chunk.rows.iter().enumerate() {
 			for (col_offset, value) in row.iter().enumerate() {
-				let format = formats.get(col_offset).unwrap_or(&default_format);
-				let col_offset = col_offset as u16;
+				let mut format = formats.get(col_offset).unwrap_or(&default_format).clone();
+				let mut plain_format = default_format.clone();
+				for (_, _, mark) in chunk.marks.iter().filter(|(r, c, _)| *r == row_idx && *c == col_offset) {
+					format = mark.apply(format);
+					plain_format = mark.apply(plain_format);
+				}//end applying any marks for this cell
+				let col = chunk_col + col_offset as u16;
 				match value {
-					DataVal::Integer(i) => sheet.write_number_with_format(chunk_row,col_offset,*i as f64,&default_format)?,
-					DataVal::Float(f) => sheet.write_number_with_format(chunk_row,col_offset, *f, format)?,
-					DataVal::String(s) => sheet.write_with_format(chunk_row, col_offset, s,&default_format)?,
+					DataVal::Integer(i) => sheet.write_number_with_format(chunk_row,col,*i as f64,&plain_format)?,
+					DataVal::Float(f) => sheet.write_number_with_format(chunk_row,col, *f, &format)?,
+					DataVal::String(s) => sheet.write_with_format(chunk_row, col, s,&plain_format)?,
+					DataVal::Empty => sheet.write_blank(chunk_row, col, &plain_format)?,
+					DataVal::Formula(formula, cached) => {
+						let formula = Formula::new(fill_formula(formula, chunk_row + 1, chunk_col))
+							.set_result(cached.result_text());
+						sheet.write_formula_with_format(chunk_row, col, formula, &format)?
+					},
 				};//end matching type of data
 			}//end looping over cells within row
 			chunk_row += 1;
 		}//end looping over the rows for this chunk
 
+		// make the chunk a table, and name it
+		if options.tables && chunk.rows.len() > 0 {
+			let mut headers: Vec<String> = chunk.headers.iter().map(|(header, _, _)| header.clone()).collect();
+			headers.resize(width, String::new());
+			let columns: Vec<TableColumn> = table_headers(headers).into_iter()
+				.map(|header| TableColumn::new().set_header(header).set_header_format(&bold))
+				.collect();
+			let table = Table::new()
+				.set_name(format!("tbl_{}", chunk_name))
+				.set_columns(&columns)
+				.set_autofilter(true)
+				.set_banded_rows(true);
+			sheet.add_table(header_row, chunk_col, chunk_row - 1, last_col, &table)?;
+		}//end if we're making tables
+		if options.named_ranges {named_ranges.push((chunk_name, header_row, chunk_row - 1, chunk_col, last_col));}
+
+		// add conditional formats over the rows just written
+		let (first_row, last_row) = (header_row + 1, chunk_row.saturating_sub(1));
+		for (cols, highlight) in chunk.highlights.iter().filter(|_| chunk.rows.len() > 0) {
+			match highlight {
+				Highlight::Threshold(rule) => for col in cols.iter().map(|col| chunk_col + *col as u16) {
+					let top_cell = format!("{}{}", column_number_to_name(col), first_row + 1);
+					let conditional_format = ConditionalFormatFormula::new()
+						.set_rule(rule.formula(&top_cell).as_str())
+						.set_format(rule.format());
+					sheet.add_conditional_format(first_row, col, last_row, col, &conditional_format)?;
+				},//end adding a format for each column
+				Highlight::ColorScale => {
+					let ranges: Vec<String> = cols.iter()
+						.map(|col| column_number_to_name(chunk_col + *col as u16))
+						.map(|col| format!("{}{}:{}{}", col, first_row + 1, col, last_row + 1))
+						.collect();
+					let conditional_format = ConditionalFormat3ColorScale::new().set_multi_range(ranges.join(" "));
+					let col = chunk_col + cols[0] as u16;
+					sheet.add_conditional_format(first_row, col, last_row, col, &conditional_format)?;
+				},
+			}//end matching kind of highlight
+		}//end adding each highlight
+
 		// loop maintenance for writing multiple chunks
-		chunk_row += 2;
+		match options.layout {
+			ChunkLayout::Vertical => chunk_row += 2,
+			ChunkLayout::SideBySide => {
+				chunk_row = 0;
+				chunk_col = right_edge + 1;
+			},
+		}//end matching where the next chunk goes
 	}//end writing each chunk of data to the sheet
 
+	// fit widths before the captions go in, so long titles don't widen the label column
+	if options.autofit {sheet.autofit();}
+	for (row, first_col, last_col, title) in captions {
+		match first_col == last_col {
+			true => sheet.write_with_format(row, first_col, title, &caption_format)?,
+			false => sheet.merge_range(row, first_col, row, last_col, &title, &caption_format)?,
+		};//end matching whether the caption spans more than one cell
+	}//end writing each caption
+	if let Some(header_row) = first_header_row.filter(|_| options.freeze_panes) {
+		sheet.set_freeze_panes(header_row + 1, 1)?;
+	}//end if we're freezing the headers and labels
+
+	// add charts beside the data, one under the other
+	let column_range = |chunk: usize, col: usize| -> Option<ChartRange> {
+		let (first_row, rows, first_col) = *chunk_spans.get(chunk)?;
+		if rows == 0 {return None;}
+		let col = first_col + col as u16;
+		Some(ChartRange::new_from_range(sheet_name, first_row, col, first_row + rows - 1, col))
+	};//end closure column_range
+	let mut chart_row = 1;
+	for sheet_chart in options.charts.iter() {
+		let Some(categories) = column_range(sheet_chart.categories.0, sheet_chart.categories.1) else {continue;};
+		let mut chart = match sheet_chart.kind {
+			ChartKind::Column => Chart::new_column(),
+			ChartKind::Line => Chart::new_line(),
+		};//end matching kind of chart
+		chart.title().set_name(&sheet_chart.title);
+		chart.y_axis().set_name(&sheet_chart.y_axis);
+		chart.set_width(720).set_height(360);
+		for series in sheet_chart.series.iter() {
+			let Some(values) = column_range(series.chunk, series.col) else {continue;};
+			let chart_series = chart.add_series()
+				.set_name(&series.name)
+				.set_categories(&categories)
+				.set_values(&values);
+			if let Some(errors) = series.error_col.and_then(|col| column_range(series.chunk, col)) {
+				chart_series.set_y_error_bars(ChartErrorBars::new().set_type(ChartErrorBarsType::Custom(errors.clone(), errors)));
+			}//end if this series has error bars
+		}//end adding each series
+		sheet.insert_chart(chart_row, right_edge + 1, &chart)?;
+		chart_row += 20;
+	}//end adding each chart
+
+	// name chunks for the whole workbook
+	for (name, first_row, last_row, first_col, last_col) in named_ranges {
+		let range = format!("='{}'!${}${}:${}${}", sheet_name, column_number_to_name(first_col), first_row + 1, column_number_to_name(last_col), last_row + 1);
+		workbook.define_name(name, &range)?;
+	}//end naming each chunk
+
 	Ok(())
 }
@@ -0,0 +1,85 @@
+use serde::Deserialize;
+
+use crate::data::{InputFile, InputLine};
+
+/// A single kernel to leave out, by file id and grid index.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize)]
+pub struct ExcludedKernel {
+	pub file_id: String,
+	pub grid_idx: i32,
+}//end struct ExcludedKernel
+
+/// Rules for leaving kernels out of statistics, like broken
+/// kernels, debris or kernels touching each other.
+/// Excluded kernels are still shown on the labelled sheet, so the
+/// exclusions can be checked.  
+/// Area limits are always in pixels, as read from the csv, even
+/// when calibration shows areas in mm².
+#[derive(Clone, PartialEq, Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct ExclusionRules {
+	/// Kernels with a smaller Area1, in pixels, are excluded.
+	pub min_area1: Option<i32>,
+	/// Kernels with a larger Area1, in pixels, are excluded.
+	pub max_area1: Option<i32>,
+	/// Kernels with a smaller %Area2 are excluded.
+	pub min_perc_area2: Option<f32>,
+	/// Kernels with a larger %Area2 are excluded.
+	pub max_perc_area2: Option<f32>,
+	/// Specific kernels to exclude.
+	pub exclude: Vec<ExcludedKernel>,
+}//end struct ExclusionRules
+
+impl ExclusionRules {
+	/// Checks that no minimum is bigger than its maximum.
+	pub fn check(&self) -> Result<(), String> {
+		if let (Some(min), Some(max)) = (self.min_area1, self.max_area1) {
+			if min > max {return Err(format!("Exclusion min_area1 ({}) is bigger than max_area1 ({})", min, max));}
+		}//end if we have an Area1 range
+		if let (Some(min), Some(max)) = (self.min_perc_area2, self.max_perc_area2) {
+			if min > max {return Err(format!("Exclusion min_perc_area2 ({}) is bigger than max_perc_area2 ({})", min, max));}
+		}//end if we have a %Area2 range
+		return Ok(());
+	}//end check()
+
+	/// The reason a line from the file with file_id should be
+	/// excluded, or None if it shouldn't be.
+	/// Rules about missing values never exclude a line.
+	///
+	/// # Examples
+	/// ```
+	/// use milo_excel_helper::{data::InputLine, exclusion::ExclusionRules};
+	/// let rules = ExclusionRules {min_area1: Some(500), max_perc_area2: Some(90.), ..Default::default()};
+	/// let line = InputLine {grid_idx: 1, area1: Some(120), ..Default::default()};
+	/// assert!(rules.reason("f1", &line).is_some());
+	/// let line = InputLine {grid_idx: 1, area1: Some(1000), perc_area2: Some(40.), ..Default::default()};
+	/// assert_eq!(rules.reason("f1", &line), None);
+	/// ```
+	pub fn reason(&self, file_id: &str, line: &InputLine) -> Option<String> {
+		if self.exclude.iter().any(|kernel| kernel.file_id == file_id && kernel.grid_idx == line.grid_idx) {
+			return Some("Excluded by the exclude list".to_string());
+		}//end if this kernel was listed
+		if let Some(area1) = line.area1 {
+			if let Some(min) = self.min_area1.filter(|min| area1 < *min) {return Some(format!("Area1 {} px is below {} px", area1, min));}
+			if let Some(max) = self.max_area1.filter(|max| area1 > *max) {return Some(format!("Area1 {} px is above {} px", area1, max));}
+		}//end if we have an Area1 to check
+		if let Some(perc_area2) = line.perc_area2 {
+			if let Some(min) = self.min_perc_area2.filter(|min| perc_area2 < *min) {return Some(format!("%Area2 {} is below {}", perc_area2, min));}
+			if let Some(max) = self.max_perc_area2.filter(|max| perc_area2 > *max) {return Some(format!("%Area2 {} is above {}", perc_area2, max));}
+		}//end if we have a %Area2 to check
+		return None;
+	}//end reason()
+
+	/// Marks every line in data which should be excluded, returning
+	/// the number of lines excluded.
+	pub fn apply(&self, data: &mut Vec<InputFile>) -> usize {
+		let mut count = 0;
+		for file in data.iter_mut() {
+			for line in file.input_lines.iter_mut() {
+				line.excluded = self.reason(&file.file_id, line);
+				if line.excluded.is_some() {count += 1;}
+			}//end checking each line
+		}//end checking each file
+		return count;
+	}//end apply()
+}//end impl for ExclusionRules
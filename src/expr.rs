@@ -0,0 +1,283 @@
+use std::fmt;
+
+use serde::Deserialize;
+
+use crate::data::InputLine;
+
+/// The names of values every line has, which expressions can use
+/// without any extra columns. Names are matched ignoring case.
+const BUILT_IN_NAMES: [&str; 6] = ["area1", "area2", "%area2", "perc_area2", "grid_idx", "grididx"];
+
+/// An arithmetic operation between two expressions.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Op {
+	Add,
+	Sub,
+	Mul,
+	Div,
+}//end enum Op
+
+/// A small arithmetic expression, evaluated for each line of data.
+/// Expressions can use numbers, + - * /, parentheses, and the
+/// values of a line by column name, like area1, area2, %area2 or
+/// grid_idx. Any other name refers to an extra column from the csv,
+/// and names with spaces can be put in brackets, like [Mean Intensity].
+/// Names are matched ignoring case.
+///
+/// # Examples
+/// ```
+/// use milo_excel_helper::{data::InputLine, expr::Expr};
+/// let line = InputLine {grid_idx: 1, area1: Some(1000), area2: Some(400), perc_area2: Some(40.), extra: vec![("Mean Intensity".to_string(), Some(12.5))], ..Default::default()};
+/// assert_eq!(Expr::parse("area1 - area2").unwrap().eval(&line), Some(600.));
+/// assert_eq!(Expr::parse("Area2 / Area1").unwrap().eval(&line), Some(0.4));
+/// assert_eq!(Expr::parse("-(area1 - 2 * [mean intensity])").unwrap().eval(&line), Some(-975.));
+/// assert_eq!(Expr::parse("area1 / (area2 - 400)").unwrap().eval(&line), None);
+/// assert_eq!(Expr::parse("perimeter * 2").unwrap().eval(&line), None);
+/// assert!(Expr::parse("area1 -").is_err());
+/// assert!(Expr::parse("[mean intensity * 2").is_err());
+/// ```
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Expr {
+	Num(f64),
+	/// The value of a column, by name.
+	Var(String),
+	Neg(Box<Expr>),
+	Bin(Op, Box<Expr>, Box<Expr>),
+}//end enum Expr
+
+impl TryFrom<String> for Expr {
+	type Error = String;
+	fn try_from(text: String) -> Result<Expr, String> {Expr::parse(&text)}
+}//end impl TryFrom<String> for Expr
+
+/// A piece of an expression, as split up before parsing.
+#[derive(Clone, PartialEq, Debug)]
+enum Token {
+	Num(f64),
+	Name(String),
+	Op(Op),
+	Open,
+	Close,
+}//end enum Token
+
+impl fmt::Display for Token {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Token::Num(num) => write!(f, "{}", num),
+			Token::Name(name) => write!(f, "{}", name),
+			Token::Op(Op::Add) => write!(f, "+"),
+			Token::Op(Op::Sub) => write!(f, "-"),
+			Token::Op(Op::Mul) => write!(f, "*"),
+			Token::Op(Op::Div) => write!(f, "/"),
+			Token::Open => write!(f, "("),
+			Token::Close => write!(f, ")"),
+		}//end matching self
+	}//end fmt()
+}//end impl Display for Token
+
+/// Splits text into tokens, or describes the first character
+/// which doesn't fit.
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+	let mut tokens = Vec::new();
+	let mut chars = text.chars().peekable();
+	while let Some(c) = chars.next() {
+		match c {
+			c if c.is_whitespace() => {},
+			'+' => tokens.push(Token::Op(Op::Add)),
+			'-' => tokens.push(Token::Op(Op::Sub)),
+			'*' => tokens.push(Token::Op(Op::Mul)),
+			'/' => tokens.push(Token::Op(Op::Div)),
+			'(' => tokens.push(Token::Open),
+			')' => tokens.push(Token::Close),
+			'[' => {
+				let mut name = String::new();
+				loop {
+					match chars.next() {
+						Some(']') => break,
+						Some(c) => name.push(c),
+						None => return Err(format!("Missing closing bracket after [{}", name)),
+					}//end matching the next character in brackets
+				}//end reading up to the closing bracket
+				if name.trim().is_empty() {return Err("Empty column name in brackets".to_string());}
+				tokens.push(Token::Name(name.trim().to_string()));
+			},
+			c if c.is_ascii_digit() || c == '.' => {
+				let mut num = c.to_string();
+				while let Some(next) = chars.next_if(|c| c.is_ascii_digit() || *c == '.') {num.push(next);}
+				let num = num.parse::<f64>().map_err(|_| format!("{} isn't a number", num))?;
+				tokens.push(Token::Num(num));
+			},
+			c if c.is_alphabetic() || c == '_' || c == '%' => {
+				let mut name = c.to_string();
+				while let Some(next) = chars.next_if(|c| c.is_alphanumeric() || *c == '_' || *c == '%') {name.push(next);}
+				tokens.push(Token::Name(name));
+			},
+			c => return Err(format!("Unexpected character {}", c)),
+		}//end matching each character
+	}//end looping over characters
+	return Ok(tokens);
+}//end tokenize()
+
+/// Recursive descent parser over a list of tokens, with the usual
+/// precedence of * and / over + and -.
+struct Parser {
+	tokens: Vec<Token>,
+	pos: usize,
+}//end struct Parser
+
+impl Parser {
+	fn peek(&self) -> Option<&Token> {self.tokens.get(self.pos)}
+	fn next(&mut self) -> Option<Token> {
+		let token = self.tokens.get(self.pos).cloned();
+		self.pos += 1;
+		return token;
+	}//end next()
+
+	/// sum := product (('+' | '-') product)*
+	fn sum(&mut self) -> Result<Expr, String> {
+		let mut expr = self.product()?;
+		while let Some(Token::Op(op @ (Op::Add | Op::Sub))) = self.peek().cloned() {
+			self.pos += 1;
+			expr = Expr::Bin(op, Box::new(expr), Box::new(self.product()?));
+		}//end while we have more terms
+		return Ok(expr);
+	}//end sum()
+
+	/// product := unary (('*' | '/') unary)*
+	fn product(&mut self) -> Result<Expr, String> {
+		let mut expr = self.unary()?;
+		while let Some(Token::Op(op @ (Op::Mul | Op::Div))) = self.peek().cloned() {
+			self.pos += 1;
+			expr = Expr::Bin(op, Box::new(expr), Box::new(self.unary()?));
+		}//end while we have more factors
+		return Ok(expr);
+	}//end product()
+
+	/// unary := '-' unary | atom
+	fn unary(&mut self) -> Result<Expr, String> {
+		match self.peek() {
+			Some(Token::Op(Op::Sub)) => {
+				self.pos += 1;
+				Ok(Expr::Neg(Box::new(self.unary()?)))
+			},
+			_ => self.atom(),
+		}//end matching whether this is negated
+	}//end unary()
+
+	/// atom := number | name | '(' sum ')'
+	fn atom(&mut self) -> Result<Expr, String> {
+		match self.next() {
+			Some(Token::Num(num)) => Ok(Expr::Num(num)),
+			Some(Token::Name(name)) => Ok(Expr::Var(name)),
+			Some(Token::Open) => {
+				let expr = self.sum()?;
+				match self.next() {
+					Some(Token::Close) => Ok(expr),
+					_ => Err("Missing closing parenthesis".to_string()),
+				}//end matching whether the parenthesis is closed
+			},
+			Some(token) => Err(format!("Unexpected {}", token)),
+			None => Err("Expression ended early".to_string()),
+		}//end matching the next token
+	}//end atom()
+}//end impl for Parser
+
+impl Expr {
+	/// Parses an expression from text, or describes why it can't be.
+	pub fn parse(text: &str) -> Result<Expr, String> {
+		let parse_all = || -> Result<Expr, String> {
+			let mut parser = Parser {tokens: tokenize(text)?, pos: 0};
+			let expr = parser.sum()?;
+			match parser.peek() {
+				Some(token) => Err(format!("Unexpected {}", token)),
+				None => Ok(expr),
+			}//end matching whether there's anything left over
+		};//end closure parse_all
+		parse_all().map_err(|err| format!("Couldn't read expression \"{}\": {}", text, err))
+	}//end parse()
+
+	/// The names this expression uses which are neither built-in
+	/// values nor among columns, ignoring case, so a typo can be
+	/// reported instead of giving a blank value for every line.
+	///
+	/// # Examples
+	/// ```
+	/// use milo_excel_helper::expr::Expr;
+	/// let expr = Expr::parse("aera2 / area1 + [Mean Intensity]").unwrap();
+	/// assert_eq!(expr.unknown_names(&vec!["mean intensity".to_string()]), vec!["aera2".to_string()]);
+	/// assert_eq!(expr.unknown_names(&Vec::new()).len(), 2);
+	/// ```
+	pub fn unknown_names(&self, columns: &Vec<String>) -> Vec<String> {
+		match self {
+			Expr::Num(_) => Vec::new(),
+			Expr::Var(name) => {
+				let known = BUILT_IN_NAMES.iter().any(|known| known.eq_ignore_ascii_case(name))
+					|| columns.iter().any(|column| column.eq_ignore_ascii_case(name));
+				match known {
+					true => Vec::new(),
+					false => vec![name.clone()],
+				}//end matching whether name is known
+			},
+			Expr::Neg(expr) => expr.unknown_names(columns),
+			Expr::Bin(_, left, right) => {
+				let mut names = left.unknown_names(columns);
+				for name in right.unknown_names(columns) {
+					if !names.contains(&name) {names.push(name);}
+				}//end adding each name not already found
+				names
+			},
+		}//end matching self
+	}//end unknown_names()
+
+	/// Evaluates this expression for a line.
+	/// Returns None if any value it uses is missing, or if the
+	/// result isn't a finite number, as when dividing by zero.
+	pub fn eval(&self, line: &InputLine) -> Option<f32> {
+		self.eval_f64(line)
+			.filter(|val| val.is_finite())
+			.map(|val| val as f32)
+	}//end eval()
+
+	fn eval_f64(&self, line: &InputLine) -> Option<f64> {
+		match self {
+			Expr::Num(num) => Some(*num),
+			Expr::Var(name) => match name.to_ascii_lowercase().as_str() {
+				"area1" => line.area1.map(f64::from),
+				"area2" => line.area2.map(f64::from),
+				"%area2" | "perc_area2" => line.perc_area2.map(f64::from),
+				"grid_idx" | "grididx" => Some(line.grid_idx as f64),
+				_ => line.get_extra(name).map(f64::from),
+			},//end matching name to a value of line
+			Expr::Neg(expr) => expr.eval_f64(line).map(|val| -val),
+			Expr::Bin(op, left, right) => {
+				let (left, right) = (left.eval_f64(line)?, right.eval_f64(line)?);
+				match op {
+					Op::Add => Some(left + right),
+					Op::Sub => Some(left - right),
+					Op::Mul => Some(left * right),
+					Op::Div => Some(left / right),
+				}//end matching operation
+			},
+		}//end matching self
+	}//end eval_f64()
+}//end impl for Expr
+
+/// A value computed from other values of each line, which can
+/// be output like any column of the csv.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+pub struct DerivedMetric {
+	/// The name used in headers and sheet names, and to pick this
+	/// metric in the output settings.
+	pub name: String,
+	/// How to compute the value for each line.
+	pub expr: Expr,
+	/// The number of decimal places to show.
+	#[serde(default = "default_decimals")]
+	pub decimals: usize,
+	/// Whether to show values as percentages, so 0.4 shows as 40%.
+	#[serde(default)]
+	pub percent: bool,
+}//end struct DerivedMetric
+
+fn default_decimals() -> usize {2}
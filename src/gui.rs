@@ -85,6 +85,12 @@ impl GUI {
         }//end matching dialog result
     }//end show_three_choice()
 
+    /// Asks the user to type in some text, with default already
+    /// filled in. Returns None if the user cancelled.
+    pub fn show_input(txt: &str, default: &str) -> Option<String> {
+        dialog::input_default(txt, default)
+    }//end show_input()
+
 	/// Gives a small visual indication that the program is doing something in the background.
     pub fn start_wait(&mut self) {
         self.ux_main_window.set_cursor(fltk::enums::Cursor::Wait);
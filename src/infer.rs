@@ -0,0 +1,141 @@
+use serde::Deserialize;
+
+use crate::{data::{InputFile, InputLine, OrderingStatus, Orderings, SampleOrder}, excel::guess_sample_id, math};
+
+/// Settings for guessing the ordering of files whose file id
+/// doesn't have any ordering indicator.
+#[derive(Clone, Copy, PartialEq, Debug, Deserialize)]
+#[serde(default)]
+pub struct InferenceSettings {
+	/// Whether to guess orderings at all.
+	pub enabled: bool,
+	/// Whether to ask the user to accept or override every guessed
+	/// ordering. On by default, so no guess is used without a look.
+	pub confirm: bool,
+	/// The confidence below which the user is asked before using a
+	/// guess, even if confirm is off. Guesses at or above it are
+	/// used, and listed on the validation sheet and in the report.
+	pub min_confidence: f32,
+}//end struct InferenceSettings
+
+impl Default for InferenceSettings {
+	fn default() -> InferenceSettings {
+		InferenceSettings {enabled: true, confirm: true, min_confidence: 0.3}
+	}//end default()
+}//end impl Default for InferenceSettings
+
+impl InferenceSettings {
+	/// Whether the user should be asked before using guess.
+	pub fn should_ask(&self, guess: &OrderingGuess) -> bool {
+		self.confirm || guess.confidence < self.min_confidence
+	}//end should_ask()
+}//end impl for InferenceSettings
+
+/// A guess at the ordering of a file, based on how well its
+/// data lines up with other files of the same sample.
+#[derive(Clone, PartialEq, Debug)]
+pub struct OrderingGuess {
+	/// The ordering which fit best.
+	pub ordering: SampleOrder,
+	/// How far ahead of the next best candidate the best one was,
+	/// from 0 (no better) to 1 (perfect fit, opposite runner-up).
+	pub confidence: f32,
+	/// Every candidate ordering with the correlation of its
+	/// arrangement against the reference, best first.
+	pub candidates: Vec<(SampleOrder, f32)>,
+	/// The ids of the files the data was compared against.
+	pub reference_ids: Vec<String>,
+}//end struct OrderingGuess
+
+/// Whether two file ids seem to come from the same sample,
+/// which is when guess_sample_id() finds a common id with a number in it.
+fn same_sample(a: &str, b: &str) -> bool {
+	match guess_sample_id(&vec![a, b]) {
+		Some(id) => id.chars().any(|c| c.is_numeric()),
+		None => false,
+	}//end matching whether we found a common id
+}//end same_sample()
+
+/// The kernel and endosperm areas of lines, as separate profiles,
+/// so that each can be compared on its own scale.
+fn area_profiles(lines: &Vec<&InputLine>) -> [Vec<Option<f32>>; 2] {
+	[
+		lines.iter().map(|line| line.area1.map(|a| a as f32)).collect(),
+		lines.iter().map(|line| line.area2.map(|a| a as f32)).collect(),
+	]
+}//end area_profiles()
+
+/// The average correlation of each profile with its reference,
+/// ignoring any which can't be calculated.
+fn profile_score(profiles: &[Vec<Option<f32>>; 2], reference: &[Vec<Option<f32>>; 2]) -> f32 {
+	let correlations: Vec<Option<f32>> = profiles.iter().zip(reference.iter())
+		.map(|(profile, reference)| math::correlation(profile, reference))
+		.map(|r| if r.is_finite() {Some(r)} else {None})
+		.collect();
+	return math::avg(&correlations);
+}//end profile_score()
+
+/// Guesses the ordering of the file at file_idx within files,
+/// if its ordering couldn't be found from its file id.  
+/// Files of the same sample with a known ordering and the same
+/// number of lines are put into canonical order and averaged to
+/// get a reference. Each ordering with the right number of labels
+/// is then tried on the file, and the one whose kernel and
+/// endosperm areas correlate best with the reference is returned.  
+/// If no other files are from the same sample, every file with a
+/// known ordering and the same number of lines is used instead.  
+/// Returns None if the file's ordering is already known, or if
+/// there's nothing to compare it against.
+pub fn guess_ordering(files: &Vec<InputFile>, file_idx: usize, orderings: &Orderings) -> Option<OrderingGuess> {
+	let file = files.get(file_idx)?;
+	if file.ordering_detection.status() != OrderingStatus::Unknown {return None;}
+	let n_lines = file.input_lines.len();
+
+	// find files to compare against, preferring files from the same sample
+	let known_files: Vec<&InputFile> = files.iter()
+		.filter(|other| other.ordering_detection.status() != OrderingStatus::Unknown)
+		.filter(|other| other.input_lines.len() == n_lines)
+		.collect();
+	let same_sample_files: Vec<&InputFile> = known_files.iter()
+		.filter(|other| same_sample(&file.file_id, &other.file_id))
+		.cloned()
+		.collect();
+	let reference_files = if same_sample_files.len() > 0 {same_sample_files} else {known_files};
+	if reference_files.len() == 0 {return None;}
+
+	// average the areas at each canonical position to get a reference
+	let profiles: Vec<[Vec<Option<f32>>; 2]> = reference_files.iter()
+		.map(|other| area_profiles(&InputFile::get_ab15_order(&other.sample_ordering, &other.input_lines)))
+		.collect();
+	let average_profile = |area_idx: usize| -> Vec<Option<f32>> {
+		(0..n_lines).map(|pos| {
+			let values: Vec<Option<f32>> = profiles.iter().map(|p| p[area_idx].get(pos).cloned().flatten()).collect();
+			match math::count(&values) {
+				0 => None,
+				_ => Some(math::avg(&values)),
+			}//end matching whether we have any values here
+		}).collect()
+	};//end closure average_profile
+	let reference = [average_profile(0), average_profile(1)];
+
+	// try each ordering which fits the file
+	let mut candidates: Vec<(SampleOrder, f32)> = orderings.list.iter()
+		.filter(|order| order.labels.len() == n_lines)
+		.map(|order| {
+			let arranged = area_profiles(&InputFile::get_ab15_order(order, &file.input_lines));
+			(order.clone(), profile_score(&arranged, &reference))
+		})
+		.filter(|(_, score)| score.is_finite())
+		.collect();
+	candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+	let (best, best_score) = candidates.first()?.clone();
+	let runner_up_score = candidates.get(1).map_or(0., |c| c.1);
+	let confidence = ((best_score - runner_up_score) / 2.).clamp(0., 1.);
+
+	Some(OrderingGuess {
+		ordering: best,
+		confidence,
+		candidates,
+		reference_ids: reference_files.iter().map(|f| f.file_id.clone()).collect(),
+	})
+}//end guess_ordering()
@@ -1,3 +1,10 @@
+pub mod calibration;
+pub mod config;
 pub mod data;
 pub mod excel;
-pub mod math;
\ No newline at end of file
+pub mod exclusion;
+pub mod expr;
+pub mod infer;
+pub mod math;
+pub mod outlier;
+pub mod validate;
\ No newline at end of file
@@ -1,7 +1,7 @@
 use gui::GUI;
 use rust_xlsxwriter::{Workbook, XlsxError};
 use std::{path::PathBuf, time::{Duration, Instant}};
-use milo_excel_helper::{data::{self, InputFile}, excel::{self, DataChunk}};
+use milo_excel_helper::{config::{self, Config}, data::{self, CsvError, InputFile, Orderings, RunMetadata, SampleOrder}, excel::{self, DataChunk, OutputSettings, OutputVal, SheetOptions}, infer::{self, InferenceSettings, OrderingGuess}, validate};
 
 mod gui;
 
@@ -14,19 +14,23 @@ fn main() {
 	println!("Written by Nicholas Sixbury for use at USDA-ARS Manhattan, KS");
 	println!("This program reformats output from the usda-java-milo-scan program.");
 	println!("\n");
+	// load any settings the user has changed
+	let config = load_config();
 	// conduct the main application loop until we exit.
 	while gui.wait() {
 		if let Some(msg) = recv.recv() {
 			match msg {
 				gui::InterfaceMessage::CSVInputFile(input_file) => {
 					gui.start_wait();
-					process_and_time_files(&vec![input_file], false);
+					let report = process_and_time_files(&vec![input_file], &config, false);
 					gui.end_wait();
+					show_report(&report);
 				},
 				gui::InterfaceMessage::CSVInputFiles(files) => {
 					gui.start_wait();
-					process_and_time_files(&files, true);
+					let report = process_and_time_files(&files, &config, true);
 					gui.end_wait();
+					show_report(&report);
 				},
 				gui::InterfaceMessage::AppClosing => GUI::quit(),
 				_ => println!("Message {:?} not recognized or supported.", msg),
@@ -35,16 +39,55 @@ fn main() {
 	}//end main app loop
 }//end main method
 
+/// Looks for a config file and loads it, falling back on
+/// default settings if there isn't one or it can't be loaded.
+fn load_config() -> Config {
+	let Some(config_path) = Config::find() else {
+		println!("No {} found, so default settings will be used.", config::CONFIG_FILE_NAME);
+		return Config::default();
+	};//end if we couldn't find a config file
+	match Config::load(&config_path) {
+		Ok(config) => {
+			println!("Loaded settings from {}", config_path.to_string_lossy());
+			config
+		},
+		Err(err) => {
+			println!("Couldn't load settings from {}, so default settings will be used.\n{}", config_path.to_string_lossy(), err);
+			GUI::show_alert(&format!("Couldn't load settings from {}, so default settings will be used.\n\n{}", config_path.to_string_lossy(), err));
+			Config::default()
+		}
+	}//end matching whether we could load the config
+}//end load_config()
+
+/// Things the user should be told about after processing
+/// a batch of files.
+#[derive(Debug, Default)]
+struct BatchReport {
+	/// Files which couldn't be read, and why.
+	failures: Vec<(PathBuf, CsvError)>,
+	/// Findings about files whose sample ordering was unknown or ambiguous.
+	ordering_findings: Vec<validate::Finding>,
+	/// Settings which couldn't be applied to a file, described with the file's name.
+	warnings: Vec<String>,
+}//end struct BatchReport
+
 /// Does all the processing for a number of input files.  
 /// Doesn't touch the gui, so you might want to do gui.start_wait()
 /// and gui.end_wait() on your own.  
+/// The config decides which header names are accepted for
+/// each column of the csv files, and which sample orderings
+/// files might have.  
 /// If output_sum_book is true, then a separate file will be created
-/// with summary information across all files given.
-fn process_and_time_files(files: &Vec<PathBuf>, output_sum_book: bool) {
-	if files.len() == 0 {println!("Can't Batch Process 0 Files !!"); return;}
-	let mut stats_k_chunks = Vec::new();
-	let mut stats_e_chunks = Vec::new();
-	let mut stats_p_chunks = Vec::new();
+/// with summary information across all files given.  
+/// Files which can't be read are skipped, and reported along
+/// with the reason they couldn't be read. Files whose sample
+/// ordering couldn't be found with certainty are reported as well.
+fn process_and_time_files(files: &Vec<PathBuf>, config: &Config, output_sum_book: bool) -> BatchReport {
+	if files.len() == 0 {println!("Can't Batch Process 0 Files !!"); return BatchReport::default();}
+	let mut report = BatchReport::default();
+	let orderings = config.get_orderings();
+	let mut stats_chunks: Vec<(OutputVal, Vec<(String, DataChunk)>)> = Vec::new();
+	let mut runs: Vec<(String, RunMetadata)> = Vec::new();
 	println!("\n\n");
 	let start = Instant::now();
 	let mut csv_duration = Duration::new(0,0);
@@ -54,27 +97,82 @@ fn process_and_time_files(files: &Vec<PathBuf>, output_sum_book: bool) {
 	for file in files.iter() {
 		// get data from file
 		let csv_instant = Instant::now();
-		let data = data::read_csv_file(&file).expect("Failed to read csv input file!?");
+		let data = data::read_csv_file(&file, &config.columns, &orderings);
 		csv_duration += csv_instant.elapsed();
+		let (mut data, mut metadata) = match data {
+			Ok(dataset) => (dataset.files, dataset.metadata),
+			Err(err) => {
+				println!("Skipping {}, as it couldn't be read. {}", file.file_name().unwrap_or_default().to_string_lossy(), err);
+				report.failures.push((file.clone(), err));
+				continue;
+			}
+		};//end matching whether we could read the file
+		let calibration = config.calibration.calibrate(&mut data).unwrap_or_else(|err| {
+			let warning = format!("{}: couldn't calibrate. {}", file.file_name().unwrap_or_default().to_string_lossy(), err);
+			println!("{}", warning);
+			report.warnings.push(warning);
+			None
+		});
+		metadata.entries.push(("Standard Deviation".to_string(), format!("{}. Split Std is always population (n)", config.output.sd_estimator.describe())));
+		if let Some(calibration) = &calibration {metadata.entries.push(("Calibration".to_string(), calibration.describe()));}
+		if config.output.outliers.enabled {metadata.entries.push(("Outliers".to_string(), config.output.outliers.describe()));}
+		if config.output.robust {metadata.entries.push(("Trimmed Mean".to_string(), format!("{}% of values left out", config.output.trim_fraction * 100.)));}
+		let output_settings = config.output.with_calibration(calibration);
+		let excluded = config.exclusion.apply(&mut data);
+		if excluded > 0 {println!("Left {} kernel(s) out of statistics because of the exclusion rules.", excluded);}
+		if config.inference.enabled {infer_unknown_orderings(&mut data, &orderings, &config.inference);}
 
-		// do processing to get data chunks
+		// check the data for problems before trusting any numbers
 		let process_start = Instant::now();
-		let detail_chunks = get_detail_chunks(&data);
-		let sum_chunks = get_sum_chunks(&data);
-		stats_k_chunks.push(sum_chunks.3.clone());
-		stats_e_chunks.push(sum_chunks.4.clone());
-		stats_p_chunks.push(sum_chunks.5.clone());
+		let findings = validate::validate_files(&data, validate::DEFAULT_PERC_TOLERANCE);
+		if findings.len() > 0 {
+			println!("Found {} potential problem(s) in {}:", findings.len(), file.file_name().unwrap_or_default().to_string_lossy());
+			for finding in findings.iter() {println!("- {}", finding);}
+		}//end if we found any problems
+		report.ordering_findings.extend(findings.iter().filter(|f| validate::is_ordering_issue(f)).cloned());
+
+		// do processing to get data chunks
+		let detail_chunks = get_detail_chunks(&data, &output_settings);
+		let source = file.file_name().unwrap_or_default().to_string_lossy().to_string();
+		let (sum_chunks, skipped_metrics) = get_sum_chunks(&data, &output_settings);
+		for reason in skipped_metrics {
+			let warning = format!("{}: {}", source, reason);
+			println!("{}", warning);
+			report.warnings.push(warning);
+		}//end reporting each metric we couldn't use
+		for (metric, _, stats_chunk) in sum_chunks.iter() {
+			match stats_chunks.iter_mut().find(|(other, _)| other.stats_sheet_name() == metric.stats_sheet_name()) {
+				Some((_, chunks)) => chunks.push((source.clone(), stats_chunk.clone())),
+				None => stats_chunks.push((metric.clone(), vec![(source.clone(), stats_chunk.clone())])),
+			}//end matching whether we have stats for this metric yet
+		}//end saving stats chunks for the summary book
+		let validation_chunk = excel::extract_validation_chunk(&findings);
+		let metrics: Vec<OutputVal> = sum_chunks.iter().map(|(metric, _, _)| metric.clone()).collect();
+		let outlier_chunk = excel::extract_outlier_chunk(&data, &metrics, &output_settings);
+		let run_info_chunk = excel::extract_run_info_chunk(&source, &metadata);
 		process_duration += process_start.elapsed();
 
 		// write all the data chunks to various excel sheets
 		let workbook_start = Instant::now();
 		let mut wb = excel::get_workbook();
 
-		write_detail_chunks(&mut wb, detail_chunks)
+		write_detail_chunks(&mut wb, detail_chunks, &output_settings)
 			.unwrap_or_else(|_| println!("Failed writing detailed chunks for {}.", file.as_os_str().to_string_lossy()));
-		write_sum_chunks(&mut wb, sum_chunks)
+		let last_stats_sheet = 3 + sum_chunks.len();
+		write_sum_chunks(&mut wb, sum_chunks, &data, &output_settings)
 			.unwrap_or_else(|_| println!("Failed writing sum chunks for {}", file.as_os_str().to_string_lossy()));
-		if let Ok(worksheet) = wb.worksheet_from_index(6) {worksheet.set_active(true);}
+		let options = SheetOptions::from_settings(&output_settings);
+		excel::write_chunks_with_options(&mut wb, vec![validation_chunk].iter(), "validation", &options)
+			.unwrap_or_else(|_| println!("Failed writing validation chunk for {}", file.as_os_str().to_string_lossy()));
+		if output_settings.outliers.enabled {
+			excel::write_chunks_with_options(&mut wb, vec![outlier_chunk].iter(), "outliers", &options)
+				.unwrap_or_else(|_| println!("Failed writing outlier chunk for {}", file.as_os_str().to_string_lossy()));
+		}//end if we looked for outliers
+		excel::write_chunks_with_options(&mut wb, vec![run_info_chunk].iter(), "run-info", &options)
+			.unwrap_or_else(|_| println!("Failed writing run info for {}", file.as_os_str().to_string_lossy()));
+		runs.push((source, metadata));
+		excel::set_run_properties(&mut wb, &runs[runs.len() - 1..]);
+		if let Ok(worksheet) = wb.worksheet_from_index(last_stats_sheet) {worksheet.set_active(true);}
 
 		// figure out output path we want for the xlsx file
 		let mut output_path = file.clone();
@@ -89,27 +187,40 @@ fn process_and_time_files(files: &Vec<PathBuf>, output_sum_book: bool) {
 		println!("Finished all processes for file {}", file.file_name().unwrap_or_default().to_string_lossy());
 	}//end doing all the processing for every file
 
-	if output_sum_book {
+	if output_sum_book && runs.len() > 0 {
 		let mut wb = excel::get_workbook();
 		let mut sum_book_output = files.first()
 			.expect("We should have files at this point").clone();
-		sum_book_output.set_file_name(format!("{}_file_summary_book", files.len()));
+		sum_book_output.set_file_name(format!("{}_file_summary_book", runs.len()));
 		sum_book_output.set_extension("xlsx");
-		excel::write_chunks_to_sheet(
-			&mut wb,
-			stats_k_chunks.iter(),
-			"kernel-stats"
-		).unwrap_or_else(|_| println!("Failed to write kernel stats to sum book."));
-		excel::write_chunks_to_sheet(
-			&mut wb,
-			stats_e_chunks.iter(),
-			"ndsprm-stats"
-		).unwrap_or_else(|_| println!("Failed to write endosperm stats to sum book."));
-		excel::write_chunks_to_sheet(
+		for (metric, sourced_chunks) in stats_chunks.iter() {
+			// title each chunk by the csv it came from, to tell them apart
+			let (sources, chunks): (Vec<String>, Vec<DataChunk>) = sourced_chunks.iter()
+				.map(|(source, chunk)| (source.clone(), DataChunk {title: Some(source.clone()), ..chunk.clone()}))
+				.unzip();
+			let sheet_name = metric.stats_sheet_name();
+			let mut options = SheetOptions::from_settings(&config.output);
+			options.named_ranges = config.output.named_ranges;
+			if config.output.charts.allows(config.output.charts.summary, &sheet_name) {
+				options.charts.extend(excel::comparison_chart(&chunks, &sources, metric));
+			}//end if this sheet gets a chart
+			excel::write_chunks_with_options(
+				&mut wb,
+				chunks.iter(),
+				&sheet_name,
+				&options
+			).unwrap_or_else(|_| println!("Failed to write {} stats to sum book.", metric.name()));
+		}//end writing stats for each metric
+		let run_info_chunks: Vec<DataChunk> = runs.iter()
+			.map(|(source, metadata)| DataChunk {title: Some(source.clone()), ..excel::extract_run_info_chunk(source, metadata)})
+			.collect();
+		excel::write_chunks_with_options(
 			&mut wb,
-			stats_p_chunks.iter(),
-			"%Area2-stats"
-		).unwrap_or_else(|_| println!("Failed to write %Area2 stats to sum book."));
+			run_info_chunks.iter(),
+			"run-info",
+			&SheetOptions::from_settings(&config.output)
+		).unwrap_or_else(|_| println!("Failed to write run info to sum book."));
+		excel::set_run_properties(&mut wb, &runs);
 		excel::close_workbook(&mut wb, &sum_book_output)
 			.unwrap_or_else(|_| println!("Failed to write changes to sum book."));
 		println!("The summary sheet should be found at {}", sum_book_output.as_os_str().to_string_lossy());
@@ -121,8 +232,76 @@ fn process_and_time_files(files: &Vec<PathBuf>, output_sum_book: bool) {
 	println!("- {} milliseconds to process data", format_milliseconds(process_duration));
 	println!("- {} milliseconds to write data to workbooks", format_milliseconds(workbook_duration));
 	println!("And {} milliseconds for all processes and all files.", format_milliseconds(total_duration));
+	if report.failures.len() > 0 {println!("{} of {} files were skipped because they couldn't be read.", report.failures.len(), files.len());}
+	return report;
 }//end process_and_time_files()
 
+/// Guesses the ordering of any files whose file id has no
+/// ordering indicator, by comparing their data to other files.  
+/// If settings say to confirm guesses, the user is asked to
+/// accept or override each one. Otherwise guesses are used as-is.
+fn infer_unknown_orderings(data: &mut Vec<InputFile>, orderings: &Orderings, settings: &InferenceSettings) {
+	for file_idx in 0..data.len() {
+		let Some(guess) = infer::guess_ordering(data, file_idx, orderings) else {continue;};
+		let file_id = data[file_idx].file_id.clone();
+		println!("{} has no ordering indicator, but looks like {} (confidence {:.2}).", file_id, guess.ordering.name, guess.confidence);
+		let chosen = match settings.should_ask(&guess) {
+			true => ask_for_ordering(&file_id, &guess, orderings),
+			false => Some(guess.ordering.clone()),
+		};//end matching whether we should ask the user
+		if let Some(ordering) = chosen {
+			data[file_idx].sample_ordering = ordering;
+			data[file_idx].inferred_ordering = Some(guess);
+		}//end if we have an ordering to use
+	}//end guessing the ordering of each file
+}//end infer_unknown_orderings()
+
+/// Asks the user which ordering to use for a file, with the
+/// guessed ordering filled in by default.  
+/// Returns None if the user would rather leave the ordering unknown.
+fn ask_for_ordering(file_id: &str, guess: &OrderingGuess, orderings: &Orderings) -> Option<SampleOrder> {
+	let candidates: Vec<String> = guess.candidates.iter()
+		.map(|(order, score)| format!("{} (correlation {:.2})", order.name, score))
+		.collect();
+	let prompt = format!(
+		"{} has no ordering indicator in its name.\nComparing its data to {} other scan(s), it looks like {} (confidence {:.2}).\n\nCandidates: {}\n\nEnter the ordering to use, or cancel to leave it unknown.",
+		file_id, guess.reference_ids.len(), guess.ordering.name, guess.confidence, candidates.join(", "));
+	loop {
+		let name = GUI::show_input(&prompt, &guess.ordering.name)?;
+		match orderings.list.iter().find(|order| order.name.eq_ignore_ascii_case(name.trim())) {
+			Some(order) => return Some(order.clone()),
+			None => GUI::show_alert(&format!("There's no ordering named \"{}\".", name.trim())),
+		}//end matching whether the user gave a real ordering
+	}//end looping until we get a real ordering or the user cancels
+}//end ask_for_ordering()
+
+/// Lets the user know about any files which couldn't be
+/// processed, and why, as well as any files whose sample
+/// ordering had to be guessed.
+fn show_report(report: &BatchReport) {
+	if report.failures.len() > 0 {
+		let mut message = String::from("The following file(s) couldn't be read, so no output was made for them:");
+		for (file, err) in report.failures.iter() {
+			message.push_str(&format!("\n\n{}\n{}", file.file_name().unwrap_or_default().to_string_lossy(), err));
+		}//end adding each failure to the message
+		GUI::show_alert(&message);
+	}//end if any files couldn't be read
+	if report.ordering_findings.len() > 0 {
+		let mut message = String::from("The sample ordering of the following scan(s) couldn't be found with certainty. Please check their labels in the output.");
+		for finding in report.ordering_findings.iter() {
+			message.push_str(&format!("\n\n{}", finding));
+		}//end adding each finding to the message
+		GUI::show_alert(&message);
+	}//end if any orderings were uncertain
+	if report.warnings.len() > 0 {
+		let mut message = String::from("Some settings couldn't be applied, so the output might not be what you expected:");
+		for warning in report.warnings.iter() {
+			message.push_str(&format!("\n\n{}", warning));
+		}//end adding each warning to the message
+		GUI::show_alert(&message);
+	}//end if any settings couldn't be applied
+}//end show_report()
+
 /// Given a duration, gives a string of a float representation of the number
 /// of milliseconds. If the parse fails, it will return the whole
 /// number of milliseconds as a string.
@@ -138,31 +317,59 @@ fn format_milliseconds(duration: Duration) -> String {
 /// - sorted_1_chunks
 /// - sorted_2_chunks
 fn get_detail_chunks(
-	data: &Vec<InputFile>
+	data: &Vec<InputFile>,
+	settings: &OutputSettings,
 ) -> (Vec<DataChunk>,Vec<DataChunk>,Vec<DataChunk>) {
-	let labelled_chunks = excel::extract_labelled_chunks(&data);
-	let sorted_1_chunks = excel::extract_sorted_chunks_1(&data);
-	let sorted_2_chunks = excel::extract_sorted_chunks_2(&data);
+	let labelled_chunks = excel::extract_labelled_chunks(&data, settings);
+	let sorted_1_chunks = excel::extract_sorted_chunks_1(&data, settings);
+	let sorted_2_chunks = excel::extract_sorted_chunks_2(&data, settings);
 	(labelled_chunks, sorted_1_chunks, sorted_2_chunks)
 }//end get_detail_chunks
 
-/// Shorthand for extracting:
-/// - sum chunk for area1
-/// - sum chunk for area2
-/// - sum_chunk for percent area
-/// - stats chunk for area1
-/// - stats_chunk for area2
-/// - stats_chunk for percent area
+/// Shorthand for extracting, for each metric in settings, skipping
+/// extra columns not found in data:
+/// - the metric
+/// - sum chunk for the metric
+/// - stats chunk for the metric
+/// 
+/// Extra columns in the metrics of settings are matched against the
+/// columns of data ignoring case, and derived metrics have the names
+/// they use checked against them. Metrics which can't be found, or
+/// which use names that can't be, are left out, and the reason for
+/// each is returned, rather than making empty sheets for them.
 fn get_sum_chunks(
-	data: &Vec<InputFile>
-) -> (DataChunk, DataChunk, DataChunk, DataChunk, DataChunk, DataChunk) {
-	let sum_k_chunk = excel::extract_sum_chunk(data, excel::OutputVal::KernelArea);
-	let sum_e_chunk = excel::extract_sum_chunk(data, excel::OutputVal::EndospermArea);
-	let sum_p_chunk = excel::extract_sum_chunk(data, excel::OutputVal::PercentArea);
-	let stats_k_chunk = excel::extract_stats_chunk(data, excel::OutputVal::KernelArea);
-	let stats_e_chunk = excel::extract_stats_chunk(data, excel::OutputVal::EndospermArea);
-	let stats_p_chunk = excel::extract_stats_chunk(data, excel::OutputVal::PercentArea);
-	(sum_k_chunk, sum_e_chunk, sum_p_chunk, stats_k_chunk, stats_e_chunk, stats_p_chunk)
+	data: &Vec<InputFile>,
+	settings: &OutputSettings,
+) -> (Vec<(OutputVal, DataChunk, DataChunk)>, Vec<String>) {
+	let found_metrics = OutputVal::all_in(data);
+	let columns: Vec<String> = found_metrics.iter()
+		.filter_map(|found| match found {OutputVal::Extra(name) => Some(name.clone()), _ => None})
+		.collect();
+	let mut chunks = Vec::new();
+	let mut skipped = Vec::new();
+	for metric in settings.metrics.iter() {
+		let metric = match metric {
+			OutputVal::Extra(name) => match columns.iter().find(|column| column.eq_ignore_ascii_case(name)) {
+				Some(column) => OutputVal::Extra(column.clone()),
+				None => {
+					skipped.push(format!("metric {} isn't a column of this csv, so it was left out.", name));
+					continue;
+				},
+			},//end matching whether the csv has this column
+			OutputVal::Derived(derived) => match derived.expr.unknown_names(&columns) {
+				unknown if unknown.is_empty() => metric.clone(),
+				unknown => {
+					skipped.push(format!("derived metric {} uses {}, which isn't a built-in value or a column of this csv, so it was left out.", derived.name, unknown.join(", ")));
+					continue;
+				},
+			},//end matching whether every name the expression uses is known
+			metric => metric.clone(),
+		};//end matching metric to a column of data
+		let sum_chunk = excel::extract_sum_chunk(data, &metric, settings);
+		let stats_chunk = excel::extract_stats_chunk(data, &metric, settings);
+		chunks.push((metric, sum_chunk, stats_chunk));
+	}//end making chunks for each metric
+	return (chunks, skipped);
 }//end get_sum_chunks
 
 /// Shorthand for writing
@@ -172,28 +379,33 @@ fn get_sum_chunks(
 fn write_detail_chunks(
 	workbook: &mut Workbook,
 	detail_chunks: (Vec<DataChunk>,Vec<DataChunk>,Vec<DataChunk>),
+	settings: &OutputSettings,
 ) -> Result<(), XlsxError> {
 	let mut found_err = Ok(());
-	excel::write_chunks_to_sheet(
+	let options = SheetOptions::from_settings(settings);
+	excel::write_chunks_with_options(
 		workbook,
 		detail_chunks.0.iter(),
-		"labelled"
+		"labelled",
+		&options
 	).unwrap_or_else(|err| {
 		found_err = Err(err);
 		println!("Failed to write labelled chunks.")
 	});
-	excel::write_chunks_to_sheet(
+	excel::write_chunks_with_options(
 		workbook,
 		detail_chunks.1.iter(),
-		"sorted-1"
+		"sorted-1",
+		&options
 	).unwrap_or_else(|err| {
 		found_err = Err(err);
 		println!("Failed to write sorted-1 chunks.")
 	});
-	excel::write_chunks_to_sheet(
+	excel::write_chunks_with_options(
 		workbook,
 		detail_chunks.2.iter(),
-		"sorted-2"
+		"sorted-2",
+		&options
 	).unwrap_or_else(|err| {
 		found_err = Err(err);
 		println!("Failed to write sorted-2 chunks.")
@@ -202,46 +414,54 @@ fn write_detail_chunks(
 }//end write_detail_chunks()
 
 /// Shorthand for writing
-/// - sum chunk
-/// - stats chunk
+/// - sum chunks, all on one sheet
+/// - stats chunk for each metric, each on its own sheet
+/// 
+/// along with whichever charts settings ask for, and names for
+/// each chunk if settings ask for named ranges.
 fn write_sum_chunks(
 	workbook: &mut Workbook,
-	sum_chunks: (DataChunk, DataChunk, DataChunk, DataChunk, DataChunk, DataChunk),
+	sum_chunks: Vec<(OutputVal, DataChunk, DataChunk)>,
+	data: &Vec<InputFile>,
+	settings: &OutputSettings,
 ) -> Result<(),XlsxError> {
 	let mut found_err = Ok(());
-	// write sum chunks
-	excel::write_chunks_to_sheet(
+	// write sum chunks, charting %Area2
+	let sum_only: Vec<DataChunk> = sum_chunks.iter()
+		.map(|(_, sum_chunk, _)| sum_chunk.clone())
+		.collect();
+	let mut options = SheetOptions::from_settings(settings);
+	options.named_ranges = settings.named_ranges;
+	let mut sum_options = options.clone();
+	sum_options.charts = sum_chunks.iter().enumerate()
+		.filter(|(_, (metric, _, _))| *metric == OutputVal::PercentArea && settings.charts.allows(settings.charts.sum, "sum"))
+		.map(|(chunk_idx, (metric, _, _))| excel::sum_chart(chunk_idx, metric, data))
+		.collect();
+	excel::write_chunks_with_options(
 		workbook,
-		vec![sum_chunks.0, sum_chunks.1, sum_chunks.2].iter(),
-		"sum"
+		sum_only.iter(),
+		"sum",
+		&sum_options
 	).unwrap_or_else(|err| {
 		found_err = Err(err);
 		println!("Failed to write sum chunks.")
 	});
 	// write stats chunks
-	excel::write_chunks_to_sheet(
-		workbook,
-		vec![sum_chunks.3].iter(),
-		"kernel-stats"
-	).unwrap_or_else(|err| {
-		found_err = Err(err);
-		println!("Failed to write kernel Area stats chunks.")
-	});
-	excel::write_chunks_to_sheet(
-		workbook,
-		vec![sum_chunks.4].iter(),
-		"ndsprm-stats"
-	).unwrap_or_else(|err| {
-		found_err = Err(err);
-		println!("Failed to write endosperm Area stats chunks.")
-	});
-	excel::write_chunks_to_sheet(
-		workbook,
-		vec![sum_chunks.5].iter(),
-		"%Area2-stats"
-	).unwrap_or_else(|err| {
-		found_err = Err(err);
-		println!("Failed to write %Area2 stats chunks.")
-	});
+	for (metric, _, stats_chunk) in sum_chunks {
+		let sheet_name = metric.stats_sheet_name();
+		let mut stats_options = options.clone();
+		if settings.charts.allows(settings.charts.stats, &sheet_name) {
+			stats_options.charts.extend(excel::stats_chart(&stats_chunk, &metric));
+		}//end if this sheet gets a chart
+		excel::write_chunks_with_options(
+			workbook,
+			vec![stats_chunk].iter(),
+			&sheet_name,
+			&stats_options
+		).unwrap_or_else(|err| {
+			found_err = Err(err);
+			println!("Failed to write {} stats chunks.", metric.name())
+		});
+	}//end writing stats chunks for each metric
 	return found_err;
 }
\ No newline at end of file
@@ -1,20 +1,421 @@
+use serde::Deserialize;
 
-pub fn avg(data: &Vec<f32>) -> f32 {
-	let sum = data.iter().sum::<f32>() as f32;
-	let count = data.len() as f32;
-	return sum / count;
+/// The number of values in data which aren't missing.
+pub fn count(data: &Vec<Option<f32>>) -> usize {
+	return data.iter().flatten().count();
+}//end count()
+
+/// The average of the values in data, skipping missing values.
+/// This is NaN if there aren't any values.
+pub fn avg(data: &Vec<Option<f32>>) -> f32 {
+	return summarize(data.iter().flatten().copied(), SdEstimator::Sample).mean.or_nan();
 }//end avg()
 
-pub fn std(data: &Vec<f32>) -> f32 {
-	let data_mean = avg(data);
-	let count = data.len();
-	let variance = data.iter().map(|value| {
-		let diff = data_mean - (*value as f32);
-		diff * diff
-	}).sum::<f32>() / count as f32;
-	return variance.sqrt();
+/// Which formula to use for the standard deviation.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SdEstimator {
+	/// Divides by n - 1, like STDEV.S in Excel. Best for replicates
+	/// sampled from a larger population.
+	#[default]
+	Sample,
+	/// Divides by n, like STDEV.P in Excel.
+	Population,
+}//end enum SdEstimator
+
+impl SdEstimator {
+	/// Describes this estimator for the output.
+	pub fn describe(&self) -> &'static str {
+		match self {
+			SdEstimator::Sample => "Sample (n-1), like STDEV.S",
+			SdEstimator::Population => "Population (n), like STDEV.P",
+		}//end matching self
+	}//end describe()
+}//end impl for SdEstimator
+
+/// A single statistic, which might not exist for some data.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Stat {
+	Value(f64),
+	/// There were no values to compute it from.
+	Empty,
+	/// There were values, but it isn't defined for them, like the
+	/// sample SD of a single value or the CV when the mean is zero.
+	Undefined,
+}//end enum Stat
+
+impl Stat {
+	/// A Value if val is a finite number, otherwise Undefined.
+	fn checked(val: f64) -> Stat {
+		match val.is_finite() {
+			true => Stat::Value(val),
+			false => Stat::Undefined,
+		}//end matching whether val can be used
+	}//end checked()
+
+	/// The value, if there is one.
+	pub fn value(&self) -> Option<f64> {
+		match self {
+			Stat::Value(val) => Some(*val),
+			_ => None,
+		}//end matching self
+	}//end value()
+
+	/// The value as an f32, or NaN if there isn't one.
+	pub fn or_nan(&self) -> f32 {
+		self.value().map_or(f32::NAN, |val| val as f32)
+	}//end or_nan()
+}//end impl for Stat
+
+/// Summary statistics of a set of values.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Summary {
+	/// The number of values.
+	pub n: usize,
+	pub mean: Stat,
+	/// The standard deviation.
+	pub sd: Stat,
+	/// The standard error of the mean, SD / sqrt(n).
+	pub se: Stat,
+	pub min: Stat,
+	pub max: Stat,
+	/// The coefficient of variation, SD / mean.
+	pub cv: Stat,
+}//end struct Summary
+
+/// Collects values one at a time, keeping a running mean and sum
+/// of squared differences in f64 with Welford's method, so that
+/// summary statistics can be had in a single pass without the
+/// rounding trouble of summing squares in f32.  
+/// Values which aren't finite numbers are skipped.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Stats {
+	n: usize,
+	mean: f64,
+	/// The sum of squared differences from the mean.
+	m2: f64,
+	min: f64,
+	max: f64,
+}//end struct Stats
+
+impl Default for Stats {
+	fn default() -> Self {Stats::new()}
+}//end impl Default for Stats
+
+impl Stats {
+	/// An accumulator without any values yet.
+	pub fn new() -> Stats {
+		Stats {n: 0, mean: 0., m2: 0., min: f64::INFINITY, max: f64::NEG_INFINITY}
+	}//end new()
+
+	/// Adds a single value, unless it's NaN or infinite.
+	pub fn push(&mut self, value: f64) {
+		if !value.is_finite() {return;}
+		self.n += 1;
+		let delta = value - self.mean;
+		self.mean += delta / self.n as f64;
+		self.m2 += delta * (value - self.mean);
+		self.min = self.min.min(value);
+		self.max = self.max.max(value);
+	}//end push()
+
+	/// The number of values added so far.
+	pub fn count(&self) -> usize {self.n}
+
+	/// The statistics of the values added so far.  
+	/// With no values everything but n is Empty. The sample SD of
+	/// a single value is Undefined, as is the CV when the mean is zero.
+	pub fn summary(&self, estimator: SdEstimator) -> Summary {
+		if self.n == 0 {
+			return Summary {n: 0, mean: Stat::Empty, sd: Stat::Empty, se: Stat::Empty, min: Stat::Empty, max: Stat::Empty, cv: Stat::Empty};
+		}//end if we don't have any values
+		let divisor = match estimator {
+			SdEstimator::Sample => self.n - 1,
+			SdEstimator::Population => self.n,
+		};//end matching estimator to what we divide by
+		let sd = match divisor {
+			0 => Stat::Undefined,
+			_ => Stat::checked((self.m2.max(0.) / divisor as f64).sqrt()),
+		};//end matching whether we can get an SD
+		let se = match sd {
+			Stat::Value(sd) => Stat::checked(sd / (self.n as f64).sqrt()),
+			other => other,
+		};//end matching whether we have an SD for the SE
+		let cv = match sd {
+			Stat::Value(_) if self.mean == 0. => Stat::Undefined,
+			Stat::Value(sd) => Stat::checked(sd / self.mean),
+			other => other,
+		};//end matching whether we can get a CV
+		return Summary {
+			n: self.n,
+			mean: Stat::checked(self.mean),
+			sd,
+			se,
+			min: Stat::Value(self.min),
+			max: Stat::Value(self.max),
+			cv,
+		};
+	}//end summary()
+}//end impl for Stats
+
+impl<T: Into<f64>> Extend<T> for Stats {
+	fn extend<I: IntoIterator<Item = T>>(&mut self, values: I) {
+		for value in values {self.push(value.into());}
+	}//end extend()
+}//end impl Extend for Stats
+
+impl<T: Into<f64>> FromIterator<T> for Stats {
+	fn from_iter<I: IntoIterator<Item = T>>(values: I) -> Self {
+		let mut stats = Stats::new();
+		stats.extend(values);
+		return stats;
+	}//end from_iter()
+}//end impl FromIterator for Stats
+
+/// The summary statistics of values, in a single pass.
+///
+/// # Examples
+/// ```
+/// use milo_excel_helper::math::{summarize, SdEstimator, Stat};
+/// let data = vec![Some(2f32), Some(4.), None, Some(6.)];
+/// let summary = summarize(data.iter().flatten().copied(), SdEstimator::Sample);
+/// assert_eq!((summary.n, summary.mean, summary.sd), (3, Stat::Value(4.), Stat::Value(2.)));
+/// assert_eq!((summary.min, summary.max, summary.cv), (Stat::Value(2.), Stat::Value(6.), Stat::Value(0.5)));
+/// let summary = summarize([5.0], SdEstimator::Sample);
+/// assert_eq!((summary.mean, summary.sd, summary.cv), (Stat::Value(5.), Stat::Undefined, Stat::Undefined));
+/// assert_eq!(summarize([-1.0, 1.0], SdEstimator::Population).cv, Stat::Undefined);
+/// assert_eq!(summarize(Vec::<f64>::new(), SdEstimator::Sample).mean, Stat::Empty);
+/// ```
+pub fn summarize<T: Into<f64>>(values: impl IntoIterator<Item = T>, estimator: SdEstimator) -> Summary {
+	return values.into_iter().collect::<Stats>().summary(estimator);
+}//end summarize()
+
+/// Statistics which one or two wild values can't pull far off,
+/// unlike the mean and SD.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct RobustSummary {
+	pub median: Stat,
+	/// The first quartile.
+	pub q1: Stat,
+	/// The third quartile.
+	pub q3: Stat,
+	/// The interquartile range, Q3 - Q1.
+	pub iqr: Stat,
+	/// The median absolute deviation from the median, unscaled.
+	pub mad: Stat,
+	/// The mean after trimming extreme values, like TRIMMEAN in Excel.
+	pub trimmed_mean: Stat,
+}//end struct RobustSummary
+
+/// Sorts values, skipping any which aren't finite numbers.
+fn sorted_finite<T: Into<f64>>(values: impl IntoIterator<Item = T>) -> Vec<f64> {
+	let mut sorted: Vec<f64> = values.into_iter()
+		.map(|value| value.into())
+		.filter(|value: &f64| value.is_finite())
+		.collect();
+	sorted.sort_by(f64::total_cmp);
+	return sorted;
+}//end sorted_finite()
+
+/// The quantile at p (from 0 to 1) of already sorted values,
+/// interpolating between neighbors like QUARTILE.INC in Excel.
+fn quantile_of_sorted(sorted: &[f64], p: f64) -> Stat {
+	if sorted.is_empty() {return Stat::Empty;}
+	let pos = p.clamp(0., 1.) * (sorted.len() - 1) as f64;
+	let (below, above) = (pos.floor() as usize, pos.ceil() as usize);
+	return Stat::checked(sorted[below] + (sorted[above] - sorted[below]) * (pos - below as f64));
+}//end quantile_of_sorted()
+
+/// The quantile at p (from 0 to 1) of values, interpolating between
+/// neighbors like QUARTILE.INC and PERCENTILE.INC in Excel.
+pub fn quantile<T: Into<f64>>(values: impl IntoIterator<Item = T>, p: f64) -> Stat {
+	return quantile_of_sorted(&sorted_finite(values), p);
+}//end quantile()
+
+/// The middle value of values, or the average of the middle two.
+pub fn median<T: Into<f64>>(values: impl IntoIterator<Item = T>) -> Stat {
+	return quantile(values, 0.5);
+}//end median()
+
+/// The median absolute deviation of values, the median distance
+/// from the median. This isn't scaled to match the SD.
+pub fn mad<T: Into<f64>>(values: impl IntoIterator<Item = T>) -> Stat {
+	let sorted = sorted_finite(values);
+	let Stat::Value(mid) = quantile_of_sorted(&sorted, 0.5) else {return Stat::Empty;};
+	return median(sorted.iter().map(|value| (value - mid).abs()));
+}//end mad()
+
+/// The mean of values after leaving out fraction of them (from 0
+/// to 1), half from the top and half from the bottom, like TRIMMEAN
+/// in Excel. The number left out is rounded down to an even number.
+pub fn trimmed_mean<T: Into<f64>>(values: impl IntoIterator<Item = T>, fraction: f64) -> Stat {
+	let sorted = sorted_finite(values);
+	let per_end = (sorted.len() as f64 * fraction.clamp(0., 1.) / 2.).floor() as usize;
+	let kept = &sorted[per_end.min(sorted.len() / 2)..sorted.len() - per_end.min(sorted.len() / 2)];
+	if kept.is_empty() {
+		return match sorted.is_empty() {true => Stat::Empty, false => Stat::Undefined};
+	}//end if nothing is left after trimming
+	return summarize(kept.iter().copied(), SdEstimator::Sample).mean;
+}//end trimmed_mean()
+
+/// The robust statistics of values, trimming fraction of them for
+/// the trimmed mean.
+///
+/// # Examples
+/// ```
+/// use milo_excel_helper::math::{robust_summarize, Stat};
+/// let summary = robust_summarize([1000f32, 1010., 990., 1005., 4000.], 0.4);
+/// assert_eq!((summary.median, summary.mad), (Stat::Value(1005.), Stat::Value(5.)));
+/// assert_eq!((summary.q1, summary.q3, summary.iqr), (Stat::Value(1000.), Stat::Value(1010.), Stat::Value(10.)));
+/// assert_eq!(summary.trimmed_mean, Stat::Value(1005.));
+/// assert_eq!(robust_summarize(Vec::<f64>::new(), 0.2).median, Stat::Empty);
+/// ```
+pub fn robust_summarize<T: Into<f64>>(values: impl IntoIterator<Item = T>, fraction: f64) -> RobustSummary {
+	let sorted = sorted_finite(values);
+	let (q1, q3) = (quantile_of_sorted(&sorted, 0.25), quantile_of_sorted(&sorted, 0.75));
+	let iqr = match (q1, q3) {
+		(Stat::Value(q1), Stat::Value(q3)) => Stat::Value(q3 - q1),
+		(other, _) => other,
+	};//end matching whether we have quartiles
+	return RobustSummary {
+		median: quantile_of_sorted(&sorted, 0.5),
+		q1,
+		q3,
+		iqr,
+		mad: mad(sorted.iter().copied()),
+		trimmed_mean: trimmed_mean(sorted.iter().copied(), fraction),
+	};
+}//end robust_summarize()
+
+/// The natural log of the gamma function, by the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+	const COEFFS: [f64; 9] = [
+		0.999_999_999_999_809_9, 676.520_368_121_885_1, -1_259.139_216_722_402_8,
+		771.323_428_777_653_1, -176.615_029_162_140_6, 12.507_343_278_686_905,
+		-0.138_571_095_265_720_12, 9.984_369_578_019_572e-6, 1.505_632_735_149_311_6e-7,
+	];
+	if x < 0.5 {
+		return (std::f64::consts::PI / (std::f64::consts::PI * x).sin()).ln() - ln_gamma(1. - x);
+	}//end if we need the reflection formula
+	let x = x - 1.;
+	let t = x + 7.5;
+	let sum = COEFFS[1..].iter().enumerate()
+		.fold(COEFFS[0], |sum, (i, coeff)| sum + coeff / (x + i as f64 + 1.));
+	return 0.5 * (2. * std::f64::consts::PI).ln() + (x + 0.5) * t.ln() - t + sum.ln();
+}//end ln_gamma()
+
+/// The regularized incomplete beta function I_x(a, b), by its
+/// continued fraction.
+fn incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+	if x <= 0. {return 0.;}
+	if x >= 1. {return 1.;}
+	if x > (a + 1.) / (a + b + 2.) {return 1. - incomplete_beta(1. - x, b, a);}
+	let front = (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1. - x).ln()).exp() / a;
+	const TINY: f64 = 1e-300;
+	let (mut c, mut d) = (1., 1. - (a + b) * x / (a + 1.));
+	if d.abs() < TINY {d = TINY;}
+	d = 1. / d;
+	let mut fraction = d;
+	for m in 1..300 {
+		let m = m as f64;
+		for numerator in [
+			m * (b - m) * x / ((a + 2. * m - 1.) * (a + 2. * m)),
+			-(a + m) * (a + b + m) * x / ((a + 2. * m) * (a + 2. * m + 1.)),
+		] {
+			d = 1. + numerator * d;
+			if d.abs() < TINY {d = TINY;}
+			c = 1. + numerator / c;
+			if c.abs() < TINY {c = TINY;}
+			d = 1. / d;
+			fraction *= d * c;
+		}//end applying the even and odd steps
+		if (d * c - 1.).abs() < 1e-15 {break;}
+	}//end evaluating the continued fraction
+	return front * fraction;
+}//end incomplete_beta()
+
+/// The probability that Student's t with df degrees of freedom is at most t.
+pub fn t_cdf(t: f64, df: f64) -> f64 {
+	let tail = 0.5 * incomplete_beta(df / (df + t * t), df / 2., 0.5);
+	return if t > 0. {1. - tail} else {tail};
+}//end t_cdf()
+
+/// The value Student's t with df degrees of freedom is at most
+/// with probability p, like T.INV in Excel.
+pub fn t_quantile(p: f64, df: f64) -> f64 {
+	if p == 0.5 {return 0.;}
+	if p < 0.5 {return -t_quantile(1. - p, df);}
+	let mut high = 1.;
+	while t_cdf(high, df) < p && high < 1e12 {high *= 2.;}
+	let mut low = 0.;
+	for _ in 0..200 {
+		let mid = (low + high) / 2.;
+		if t_cdf(mid, df) < p {low = mid;} else {high = mid;}
+	}//end narrowing down the quantile
+	return (low + high) / 2.;
+}//end t_quantile()
+
+/// The critical value for the two-sided Grubbs test of n values at
+/// significance alpha, or None for fewer than three values.
+///
+/// # Examples
+/// ```
+/// use milo_excel_helper::math::grubbs_critical;
+/// assert!((grubbs_critical(3, 0.05).unwrap() - 1.1543).abs() < 1e-3);
+/// assert!((grubbs_critical(10, 0.05).unwrap() - 2.2900).abs() < 1e-3);
+/// assert_eq!(grubbs_critical(2, 0.05), None);
+/// ```
+pub fn grubbs_critical(n: usize, alpha: f64) -> Option<f64> {
+	if n < 3 {return None;}
+	let n = n as f64;
+	let t = t_quantile(1. - alpha / (2. * n), n - 2.);
+	return Some((n - 1.) / n.sqrt() * (t * t / (n - 2. + t * t)).sqrt());
+}//end grubbs_critical()
+
+/// The standard deviation of the values in data, skipping missing values.
+/// With the sample estimator, this is NaN for fewer than two values.
+/// 
+/// # Examples
+/// ```
+/// use milo_excel_helper::math::{std, SdEstimator};
+/// let data = vec![Some(2.), Some(4.), None, Some(6.)];
+/// assert_eq!(std(&data, SdEstimator::Sample), 2.);
+/// assert!((std(&data, SdEstimator::Population) - 1.63299).abs() < 1e-4);
+/// assert!(std(&vec![Some(1.)], SdEstimator::Sample).is_nan());
+/// ```
+pub fn std(data: &Vec<Option<f32>>, estimator: SdEstimator) -> f32 {
+	return summarize(data.iter().flatten().copied(), estimator).sd.or_nan();
 }//end std()
 
-pub fn cv(data: &Vec<f32>) -> f32 {
-	return std(data) / avg(data);
-}//end cv()
\ No newline at end of file
+/// The coefficient of variation of the values in data, skipping missing values.
+/// This is NaN when the standard deviation is, or when the mean is zero.
+pub fn cv(data: &Vec<Option<f32>>, estimator: SdEstimator) -> f32 {
+	return summarize(data.iter().flatten().copied(), estimator).cv.or_nan();
+}//end cv()
+
+/// The Pearson correlation between two sets of values, using
+/// only the positions where neither value is missing.  
+/// Returns NaN if fewer than two positions can be used, or if
+/// either set of values doesn't vary.
+///
+/// # Examples
+/// ```
+/// use milo_excel_helper::math::correlation;
+/// let a = vec![Some(16_000_001.), Some(16_000_003.), None, Some(16_000_007.)];
+/// let b = vec![Some(1.), Some(3.), Some(5.), Some(7.)];
+/// assert!((correlation(&a, &b) - 1.).abs() < 1e-6);
+/// assert!(correlation(&a, &vec![Some(2.); 4]).is_nan());
+/// ```
+pub fn correlation(a: &Vec<Option<f32>>, b: &Vec<Option<f32>>) -> f32 {
+	// accumulate in f64 like Stats, so large areas don't lose precision
+	let pairs: Vec<(f64, f64)> = a.iter().zip(b.iter())
+		.filter_map(|(a, b)| Some(((*a)? as f64, (*b)? as f64)))
+		.collect();
+	if pairs.len() < 2 {return f32::NAN;}
+	let a_mean = pairs.iter().map(|(a, _)| a).sum::<f64>() / pairs.len() as f64;
+	let b_mean = pairs.iter().map(|(_, b)| b).sum::<f64>() / pairs.len() as f64;
+	let covariance = pairs.iter().map(|(a, b)| (a - a_mean) * (b - b_mean)).sum::<f64>();
+	let a_variance = pairs.iter().map(|(a, _)| (a - a_mean) * (a - a_mean)).sum::<f64>();
+	let b_variance = pairs.iter().map(|(_, b)| (b - b_mean) * (b - b_mean)).sum::<f64>();
+	return (covariance / (a_variance * b_variance).sqrt()) as f32;
+}//end correlation()
\ No newline at end of file
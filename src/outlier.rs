@@ -0,0 +1,156 @@
+use serde::Deserialize;
+
+use crate::math::{self, SdEstimator, Stat};
+
+/// Scale which makes the MAD comparable to the SD for normal data.
+const MAD_SCALE: f64 = 0.6745;
+/// Scale which makes the mean absolute deviation comparable to the
+/// SD for normal data, used when the MAD is zero.
+const MEAN_AD_SCALE: f64 = 1.253314;
+
+/// How outliers are found among replicate values.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OutlierMethod {
+	/// Flags values more than threshold SDs from the mean. With n
+	/// values no z-score can be above (n-1)/sqrt(n), so this needs
+	/// quite a few replicates to flag anything.
+	ZScore,
+	/// Grubbs' test at significance alpha, repeated after removing
+	/// each outlier found until no more are found.
+	Grubbs,
+	/// Flags values whose modified z-score, based on the median and
+	/// MAD, is above threshold. Works even with only a few replicates.
+	#[default]
+	ModifiedZ,
+}//end enum OutlierMethod
+
+/// Settings for flagging values which are far off from the other
+/// scans of the same sample.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+#[serde(default)]
+pub struct OutlierSettings {
+	/// Whether to look for outliers at all. When on, outliers are
+	/// highlighted on the sum sheet and listed on an outliers sheet.
+	pub enabled: bool,
+	pub method: OutlierMethod,
+	/// The score above which a value is an outlier, for z_score and
+	/// modified_z. Defaults to 3 for z_score and 3.5 for modified_z.
+	pub threshold: Option<f64>,
+	/// The significance level for grubbs.
+	pub alpha: f64,
+}//end struct OutlierSettings
+
+impl Default for OutlierSettings {
+	fn default() -> OutlierSettings {
+		OutlierSettings {
+			enabled: false,
+			method: OutlierMethod::default(),
+			threshold: None,
+			alpha: 0.05,
+		}
+	}//end default()
+}//end impl Default for OutlierSettings
+
+impl OutlierSettings {
+	/// Checks that the threshold is positive and alpha is between 0 and 1.
+	pub fn check(&self) -> Result<(), String> {
+		if self.threshold.is_some_and(|threshold| !(threshold > 0.)) {
+			return Err("Outlier threshold must be more than 0".to_string());
+		}//end if threshold doesn't make sense
+		if !(self.alpha > 0. && self.alpha < 1.) {
+			return Err(format!("Outlier alpha ({}) must be between 0 and 1", self.alpha));
+		}//end if alpha doesn't make sense
+		return Ok(());
+	}//end check()
+
+	/// The threshold used for the z_score and modified_z methods.
+	fn threshold(&self) -> f64 {
+		match self.method {
+			OutlierMethod::ZScore => self.threshold.unwrap_or(3.),
+			_ => self.threshold.unwrap_or(3.5),
+		}//end matching method to its default threshold
+	}//end threshold()
+
+	/// Describes these settings for the output, like
+	/// Modified z-score above 3.5.
+	pub fn describe(&self) -> String {
+		match self.method {
+			OutlierMethod::ZScore => format!("Z-score above {}", self.threshold()),
+			OutlierMethod::Grubbs => format!("Grubbs' test at alpha {}", self.alpha),
+			OutlierMethod::ModifiedZ => format!("Modified z-score above {}", self.threshold()),
+		}//end matching method
+	}//end describe()
+
+	/// Finds the outliers among values, skipping missing values.
+	/// Returns the index in values of each outlier, along with its
+	/// score. At least three values are needed to find any outliers.
+	///
+	/// # Examples
+	/// ```
+	/// use milo_excel_helper::outlier::{OutlierMethod, OutlierSettings};
+	/// let values = vec![Some(1000.), Some(1010.), None, Some(990.), Some(1500.)];
+	/// assert!(OutlierSettings::default().find(&values).is_empty());
+	/// let settings = OutlierSettings {enabled: true, ..Default::default()};
+	/// let found: Vec<usize> = settings.find(&values).iter().map(|(idx, _)| *idx).collect();
+	/// assert_eq!(found, vec![4]);
+	/// let settings = OutlierSettings {enabled: true, method: OutlierMethod::ZScore, ..Default::default()};
+	/// assert!(settings.find(&values).is_empty());
+	/// ```
+	pub fn find(&self, values: &Vec<Option<f32>>) -> Vec<(usize, f64)> {
+		if !self.enabled {return Vec::new();}
+		let present: Vec<(usize, f64)> = values.iter().enumerate()
+			.filter_map(|(idx, val)| val.map(|val| (idx, val as f64)))
+			.filter(|(_, val)| val.is_finite())
+			.collect();
+		if present.len() < 3 {return Vec::new();}
+		match self.method {
+			OutlierMethod::ZScore => z_scores(&present).into_iter()
+				.filter(|(_, score)| *score > self.threshold())
+				.collect(),
+			OutlierMethod::ModifiedZ => modified_z_scores(&present).into_iter()
+				.filter(|(_, score)| *score > self.threshold())
+				.collect(),
+			OutlierMethod::Grubbs => grubbs(present, self.alpha),
+		}//end matching method
+	}//end find()
+}//end impl for OutlierSettings
+
+/// The absolute z-score of each value, or nothing if they don't vary.
+fn z_scores(values: &Vec<(usize, f64)>) -> Vec<(usize, f64)> {
+	let summary = math::summarize(values.iter().map(|(_, val)| *val), SdEstimator::Sample);
+	let (Stat::Value(mean), Stat::Value(sd)) = (summary.mean, summary.sd) else {return Vec::new();};
+	if sd == 0. {return Vec::new();}
+	return values.iter().map(|(idx, val)| (*idx, (val - mean).abs() / sd)).collect();
+}//end z_scores()
+
+/// The absolute modified z-score of each value, from the median
+/// and MAD, or nothing if they don't vary.
+fn modified_z_scores(values: &Vec<(usize, f64)>) -> Vec<(usize, f64)> {
+	let vals = values.iter().map(|(_, val)| *val);
+	let (Stat::Value(median), Stat::Value(mad)) = (math::median(vals.clone()), math::mad(vals.clone())) else {return Vec::new();};
+	let spread = match mad {
+		0. => {
+			// more than half the values are the same, so fall back on the mean absolute deviation
+			let mean_ad = math::summarize(vals.map(|val| (val - median).abs()), SdEstimator::Sample).mean.value().unwrap_or(0.);
+			MEAN_AD_SCALE * mean_ad
+		},
+		mad => mad / MAD_SCALE,
+	};//end matching whether we can use the MAD
+	if spread == 0. {return Vec::new();}
+	return values.iter().map(|(idx, val)| (*idx, (val - median).abs() / spread)).collect();
+}//end modified_z_scores()
+
+/// Repeatedly runs Grubbs' test, removing the most extreme value
+/// each time it's an outlier.
+fn grubbs(mut values: Vec<(usize, f64)>, alpha: f64) -> Vec<(usize, f64)> {
+	let mut outliers = Vec::new();
+	while let Some(critical) = math::grubbs_critical(values.len(), alpha) {
+		let scores = z_scores(&values);
+		let Some(&(idx, score)) = scores.iter().max_by(|(_, a), (_, b)| a.total_cmp(b)) else {break;};
+		if score <= critical {break;}
+		outliers.push((idx, score));
+		values.retain(|(other, _)| *other != idx);
+	}//end looking for outliers while we have enough values
+	return outliers;
+}//end grubbs()
@@ -0,0 +1,244 @@
+use std::fmt;
+
+use crate::data::{self, InputFile, InputLine, OrderingStatus};
+
+/// How far, in percentage points, %Area2 can be from
+/// Area2 / Area1 * 100 before we consider it a problem.
+pub const DEFAULT_PERC_TOLERANCE: f32 = 0.5;
+
+/// A single problem found with the data of one input file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Issue {
+	/// This many rows repeated an earlier row for the same file id
+	/// exactly, so they were left out.
+	RepeatedRows(usize),
+	/// No line has this grid index, even though indices on either side of it exist.
+	MissingGridIdx(i32),
+	/// A later row for the same file id and grid index had different
+	/// values. Holds the line which was kept and the one left out.
+	ConflictingDuplicate {kept: InputLine, dropped: InputLine},
+	/// A line has a bigger endosperm area than kernel area.
+	Area2ExceedsArea1 {grid_idx: i32, area1: i32, area2: i32},
+	/// A value was empty in the csv, so it's missing.
+	MissingValue {grid_idx: i32, column: &'static str},
+	/// A value is negative, which doesn't make sense for an area.
+	NegativeValue {grid_idx: i32, column: &'static str, value: f32},
+	/// %Area2 doesn't agree with Area2 / Area1 * 100.
+	PercentMismatch {grid_idx: i32, perc_area2: f32, expected: f32},
+	/// The number of lines doesn't match the number of sample labels for the file's ordering.
+	LineCountMismatch {lines: usize, labels: usize},
+	/// The file's layout has different sample labels than most
+	/// other files in the batch, so some rows will be blank for it.
+	MixedLayout {positions: usize, common_positions: usize},
+	/// No ordering indicator was found in the file id.
+	UnknownOrdering,
+	/// No ordering indicator was found in the file id, so the
+	/// ordering was guessed from the data with some confidence.
+	InferredOrdering {chosen: String, confidence: f32},
+	/// No ordering indicator was found in the file id, and the
+	/// user picked an ordering other than the guessed one.
+	OverriddenOrdering {chosen: String, guessed: String},
+	/// More than one ordering matched the file id. Holds the
+	/// name of the ordering used, and a description of each match.
+	AmbiguousOrdering {chosen: String, matches: Vec<String>},
+	/// The only ordering matching the file id matched when ignoring
+	/// case. Holds the name of the ordering and the components of
+	/// the file id it matched.
+	OrderingCaseMismatch {chosen: String, tokens: Vec<String>},
+}//end enum Issue
+
+impl Issue {
+	/// The grid index this issue is about, if it's about a single line.
+	pub fn grid_idx(&self) -> Option<i32> {
+		match self {
+			Issue::RepeatedRows(_) => None,
+			Issue::MissingGridIdx(grid_idx) => Some(*grid_idx),
+			Issue::ConflictingDuplicate {kept, ..} => Some(kept.grid_idx),
+			Issue::Area2ExceedsArea1 {grid_idx, ..} => Some(*grid_idx),
+			Issue::MissingValue {grid_idx, ..} => Some(*grid_idx),
+			Issue::NegativeValue {grid_idx, ..} => Some(*grid_idx),
+			Issue::PercentMismatch {grid_idx, ..} => Some(*grid_idx),
+			Issue::LineCountMismatch {..} => None,
+			Issue::MixedLayout {..} => None,
+			Issue::UnknownOrdering => None,
+			Issue::InferredOrdering {..} => None,
+			Issue::OverriddenOrdering {..} => None,
+			Issue::AmbiguousOrdering {..} => None,
+			Issue::OrderingCaseMismatch {..} => None,
+		}//end matching self
+	}//end grid_idx()
+}//end impl for Issue
+
+/// Describes the measurements of a line for a message, like
+/// Area1 1000, Area2 400, %Area2 40.
+fn describe_values(line: &InputLine) -> String {
+	fn or_blank<T: ToString>(val: Option<T>) -> String {val.map_or("blank".to_string(), |val| val.to_string())}
+	format!("Area1 {}, Area2 {}, %Area2 {}", or_blank(line.area1), or_blank(line.area2), or_blank(line.perc_area2))
+}//end describe_values()
+
+impl fmt::Display for Issue {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Issue::RepeatedRows(count) => write!(f, "{} row(s) repeat earlier rows exactly and were left out. The file may have been exported more than once", count),
+			Issue::MissingGridIdx(grid_idx) => write!(f, "GridIdx {} is missing", grid_idx),
+			Issue::ConflictingDuplicate {kept, dropped} => write!(f, "GridIdx {} appears again with different values ({}), so only the first ({}) is used", kept.grid_idx, describe_values(dropped), describe_values(kept)),
+			Issue::Area2ExceedsArea1 {area1, area2, ..} => write!(f, "Area2 ({}) is larger than Area1 ({})", area2, area1),
+			Issue::MissingValue {column, ..} => write!(f, "{} is missing", column),
+			Issue::NegativeValue {column, value, ..} => write!(f, "{} is negative ({})", column, value),
+			Issue::PercentMismatch {perc_area2, expected, ..} => write!(f, "%Area2 is {}, but Area2/Area1 gives {:.2}", perc_area2, expected),
+			Issue::LineCountMismatch {lines, labels} => write!(f, "File has {} lines, but its ordering has {} sample labels", lines, labels),
+			Issue::MixedLayout {positions, common_positions} => write!(f, "File's layout has {} positions, but most files in this batch have a different layout with {}. Samples missing from either are left blank", positions, common_positions),
+			Issue::UnknownOrdering => write!(f, "No sample ordering could be found from the file id"),
+			Issue::InferredOrdering {chosen, confidence} => write!(f, "No sample ordering indicator in the file id, so {} was guessed from the data (confidence {:.2})", chosen, confidence),
+			Issue::OverriddenOrdering {chosen, guessed} => write!(f, "No sample ordering indicator in the file id. {} was guessed from the data, but {} was chosen instead", guessed, chosen),
+			Issue::AmbiguousOrdering {chosen, matches} => write!(f, "Sample ordering is ambiguous, matching {}. Using {}", matches.join("; "), chosen),
+			Issue::OrderingCaseMismatch {chosen, tokens} => write!(f, "Sample ordering {} was found from {} only when ignoring case", chosen, tokens.join(", ")),
+		}//end matching self
+	}//end fmt()
+}//end impl Display for Issue
+
+/// An issue, along with the file it was found in.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Finding {
+	pub file_id: String,
+	pub issue: Issue,
+}//end struct Finding
+
+impl fmt::Display for Finding {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}: {}", self.file_id, self.issue)
+	}//end fmt()
+}//end impl Display for Finding
+
+/// Checks the data of a single file for problems which would
+/// make its numbers untrustworthy.  
+/// perc_tolerance is how far, in percentage points, %Area2 can
+/// be from Area2 / Area1 * 100 before it's flagged.
+pub fn validate_file(file: &InputFile, perc_tolerance: f32) -> Vec<Finding> {
+	let mut issues = Vec::new();
+
+	// check that grid indices are without gaps, since the reader already left out repeats
+	let mut grid_idxs: Vec<i32> = file.input_lines.iter()
+		.map(|line| line.grid_idx)
+		.collect();
+	grid_idxs.sort_unstable();
+	for pair in grid_idxs.windows(2) {
+		for missing in (pair[0] + 1)..pair[1] {issues.push(Issue::MissingGridIdx(missing));}
+	}//end looking at each pair of neighboring grid indices
+	if file.repeated_lines > 0 {issues.push(Issue::RepeatedRows(file.repeated_lines));}
+
+	// check for rows which disagreed with an earlier row for the same line
+	for dropped in file.conflicting_lines.iter() {
+		if let Some(kept) = file.input_lines.iter().find(|line| line.grid_idx == dropped.grid_idx) {
+			issues.push(Issue::ConflictingDuplicate {kept: kept.clone(), dropped: dropped.clone()});
+		}//end if we can find the line which was kept
+	}//end checking each conflicting line
+
+	// check each line on its own
+	for line in file.input_lines.iter() {
+		let grid_idx = line.grid_idx;
+		let values = [
+			("Area1", line.area1.map(|area| area as f32)),
+			("Area2", line.area2.map(|area| area as f32)),
+			("%Area2", line.perc_area2),
+		];
+		for (column, value) in values {
+			match value {
+				None => issues.push(Issue::MissingValue {grid_idx, column}),
+				Some(value) if value < 0. => issues.push(Issue::NegativeValue {grid_idx, column, value}),
+				Some(_) => {},
+			}//end matching whether value is missing or negative
+		}//end checking each value for missing and negative values
+		let (Some(area1), Some(area2), Some(perc_area2)) = (line.area1, line.area2, line.perc_area2) else {continue;};
+
+		if area2 > area1 {
+			issues.push(Issue::Area2ExceedsArea1 {grid_idx, area1, area2});
+		}//end if area2 is too big
+		if area1 > 0 {
+			let expected = area2 as f32 / area1 as f32 * 100.;
+			if (expected - perc_area2).abs() > perc_tolerance {
+				issues.push(Issue::PercentMismatch {grid_idx, perc_area2, expected});
+			}//end if %Area2 is too far off
+		}//end if we can calculate what %Area2 should be
+	}//end checking each line
+
+	// check that the ordering was found without guesswork
+	match file.ordering_detection.status() {
+		OrderingStatus::Matched => {},
+		OrderingStatus::Unknown => match &file.inferred_ordering {
+			None => issues.push(Issue::UnknownOrdering),
+			Some(guess) if guess.ordering == file.sample_ordering => issues.push(Issue::InferredOrdering {
+				chosen: guess.ordering.name.clone(),
+				confidence: guess.confidence,
+			}),
+			Some(guess) => issues.push(Issue::OverriddenOrdering {
+				chosen: file.sample_ordering.name.clone(),
+				guessed: guess.ordering.name.clone(),
+			}),
+		},//end matching whether we guessed the ordering
+		OrderingStatus::MatchedIgnoringCase => issues.push(Issue::OrderingCaseMismatch {
+			chosen: file.sample_ordering.name.clone(),
+			tokens: file.ordering_detection.matches.iter().flat_map(|m| m.tokens.clone()).collect(),
+		}),
+		OrderingStatus::Ambiguous => issues.push(Issue::AmbiguousOrdering {
+			chosen: file.sample_ordering.name.clone(),
+			matches: file.ordering_detection.matches.iter().map(|m| m.to_string()).collect(),
+		}),
+	}//end matching how the ordering was found
+
+	// check that we have the right number of lines for the ordering
+	if let Some(labels) = file.expected_lines() {
+		if file.input_lines.len() != labels {
+			issues.push(Issue::LineCountMismatch {lines: file.input_lines.len(), labels});
+		}//end if line count doesn't match label count
+	}//end if we know how many lines to expect
+
+	return issues.into_iter()
+		.map(|issue| Finding {file_id: file.file_id.clone(), issue})
+		.collect();
+}//end validate_file()
+
+/// Finds files whose layout (the set of sample labels from their
+/// ordering) is different from the most common layout in files.  
+/// Files with an unknown ordering are ignored, since they're
+/// reported on their own.
+pub fn check_layouts(files: &Vec<InputFile>) -> Vec<Finding> {
+	let layouts: Vec<Option<Vec<String>>> = files.iter()
+		.map(|file| file.expected_lines().map(|_| data::sort_labels(file.sample_ordering.labels.clone())))
+		.collect();
+	let mut layout_counts: Vec<(&Vec<String>, usize)> = Vec::new();
+	for layout in layouts.iter().flatten() {
+		match layout_counts.iter_mut().find(|(l, _)| *l == layout) {
+			Some((_, count)) => *count += 1,
+			None => layout_counts.push((layout, 1)),
+		}//end matching whether we've seen this layout
+	}//end counting each layout
+	let Some((common, _)) = layout_counts.iter().rev().max_by_key(|(_, count)| *count) else {return Vec::new();};
+
+	files.iter().zip(layouts.iter())
+		.filter_map(|(file, layout)| match layout {
+			Some(layout) if layout != *common => Some(Finding {
+				file_id: file.file_id.clone(),
+				issue: Issue::MixedLayout {positions: layout.len(), common_positions: common.len()},
+			}),
+			_ => None,
+		})
+		.collect()
+}//end check_layouts()
+
+/// Runs validate_file() on each file, as well as check_layouts()
+/// on all of them, returning all the findings together.
+pub fn validate_files(files: &Vec<InputFile>, perc_tolerance: f32) -> Vec<Finding> {
+	let mut findings: Vec<Finding> = files.iter()
+		.flat_map(|file| validate_file(file, perc_tolerance))
+		.collect();
+	findings.extend(check_layouts(files));
+	return findings;
+}//end validate_files()
+
+/// Whether a finding is about a file's sample ordering being
+/// unknown, ambiguous, guessed or only matched ignoring case,
+/// rather than about its data.
+pub fn is_ordering_issue(finding: &Finding) -> bool {
+	matches!(finding.issue, Issue::UnknownOrdering | Issue::AmbiguousOrdering {..} | Issue::InferredOrdering {..} | Issue::OverriddenOrdering {..} | Issue::OrderingCaseMismatch {..})
+}//end is_ordering_issue()